@@ -0,0 +1,55 @@
+use sha2::{Digest, Sha256};
+
+// A commitment is a hex-encoded SHA-256 digest
+pub const COMMITMENT_LEN: usize = 64;
+
+// The commitment a client sends before revealing `choice`: lowercase hex SHA-256 of "{choice}:{nonce}"
+pub fn commitment_hash(choice: &str, nonce: &str) -> String {
+    let digest = Sha256::digest(format!("{}:{}", choice, nonce).as_bytes());
+    digest.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+// Lowercase a client-supplied commitment, or `None` if it is not a hex SHA-256 digest
+pub fn normalize_commitment(hash: &str) -> Option<String> {
+    let hash = hash.trim();
+    if hash.len() != COMMITMENT_LEN || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    Some(hash.to_ascii_lowercase())
+}
+
+// Whether revealing `choice` with `nonce` opens `commitment`
+pub fn verify(commitment: &str, choice: &str, nonce: &str) -> bool {
+    commitment_hash(choice, nonce) == commitment
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROCK_N1: &str = "9b8a08184d662c2998ee690681e88abb4f0af294c97ccaef09041c563e2c0609";
+
+    #[test]
+    fn commitment_hash_is_the_hex_sha256_of_choice_and_nonce() {
+        assert_eq!(commitment_hash("rock", "n1"), ROCK_N1);
+        assert_eq!(commitment_hash("rock", "n1").len(), COMMITMENT_LEN);
+        assert_ne!(commitment_hash("Rock", "n1"), ROCK_N1);
+    }
+
+    #[test]
+    fn verify_needs_the_committed_choice_and_nonce() {
+        assert!(verify(ROCK_N1, "rock", "n1"));
+        assert!(!verify(ROCK_N1, "paper", "n1"));
+        assert!(!verify(ROCK_N1, "rock", "n2"));
+        assert!(!verify(ROCK_N1, "rock", "n1 "));
+    }
+
+    #[test]
+    fn normalize_commitment_accepts_only_hex_sha256_digests() {
+        assert_eq!(normalize_commitment(&format!(" {} ", ROCK_N1.to_uppercase())).as_deref(), Some(ROCK_N1));
+        assert_eq!(normalize_commitment(&ROCK_N1[1..]), None);
+        assert_eq!(normalize_commitment(&format!("{}0", ROCK_N1)), None);
+        assert_eq!(normalize_commitment(&ROCK_N1.replace('9', "g")), None);
+        assert_eq!(normalize_commitment(""), None);
+    }
+}
@@ -0,0 +1,1223 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use uuid::Uuid;
+use crate::engine::commit;
+use crate::engine::phase::RoomPhase;
+use crate::engine::rules::RuleSet;
+use crate::engine::scoring::{score_pairwise, MatchFormat, ScoreRow, Scoreboard, ScoringMode};
+use crate::engine::settings::{GameSettings, TimeoutPolicy};
+
+pub type PlayerId = Uuid;
+
+pub const MIN_PLAYERS: usize = 2;
+
+// Inputs to the game state machine
+#[derive(Debug, Clone)]
+pub enum Command {
+    // Begin a new match with the given players
+    Start { players: Vec<PlayerId>, format: MatchFormat, mode: ScoringMode },
+    // Submit a move for the current round
+    Move { player: PlayerId, choice: String },
+    // Make the submitted move final (rooms with `MoveChangePolicy::UntilLockIn`)
+    LockIn { player: PlayerId },
+    // Commit to a move without disclosing it (commit-reveal rooms); `hash` is `commit::commitment_hash(choice, nonce)`
+    Commit { player: PlayerId, hash: String },
+    // Open a commitment once the round is in the reveal phase
+    Reveal { player: PlayerId, choice: String, nonce: String },
+    // Replace the room's settings; only between matches
+    UpdateSettings { settings: GameSettings },
+    // The player left the room; they forfeit and the match goes on without them
+    Leave { player: PlayerId },
+    // The countdown of round `round` is over; start accepting moves
+    OpenSubmissions { round: u32 },
+    // The deadline of round `round` passed; `entropy` seeds random moves under `TimeoutPolicy::RandomMove`
+    RoundTimeout { round: u32, entropy: u64 },
+}
+
+// Outputs of the game state machine, in the order they happened
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    PhaseChanged { from: RoomPhase, to: RoomPhase },
+    SettingsChanged { settings: GameSettings },
+    // Round `round` counts down for `secs` seconds; the caller sends `OpenSubmissions` when it is over
+    CountdownStarted { round: u32, secs: u64 },
+    // Round `round` is open for moves; the caller arms its deadline, if any
+    RoundOpened { round: u32 },
+    // Round `round` of a commit-reveal room is open for reveals; the caller arms its deadline, if any
+    RevealOpened { round: u32 },
+    // `choices` lists the moves legal under the game's rule set; `game_number` counts games within the match
+    GameStarted { players: Vec<PlayerId>, rules: String, choices: Vec<String>, format: MatchFormat, mode: ScoringMode, game_number: u32 },
+    // The round did not produce a single winner; `next_players` play again
+    Rematch { next_players: Vec<PlayerId>, reason: RematchReason, moves: HashMap<PlayerId, String> },
+    // A single winner was determined and the game is over
+    RoundResult { winner: PlayerId, moves: HashMap<PlayerId, String> },
+    // A round-robin round was scored; `table` has one row per match player
+    RoundScored { round: u32, moves: HashMap<PlayerId, String>, table: Vec<ScoreRow> },
+    // Match standings after each finished game
+    ScoreUpdate { scores: HashMap<PlayerId, u32>, games_played: u32 },
+    // The match is decided
+    MatchResult { winner: PlayerId, scores: HashMap<PlayerId, u32> },
+    // The player is out of the current game
+    PlayerForfeited { player: PlayerId, reason: ForfeitReason },
+    // The player's move (or commitment) was accepted; only the submitter needs to know
+    MoveAccepted { player: PlayerId, round: u32 },
+    // Who has moved (or committed) in the current round so far, without revealing any move
+    RoundProgress { round: u32, submitted: Vec<PlayerId>, waiting: Vec<PlayerId> },
+    // The player locked in their move; `locked` lists everyone who has locked in this round
+    MoveLocked { player: PlayerId, locked: Vec<PlayerId> },
+    // The player committed to a move; `committed` lists everyone who has committed this round
+    MoveCommitted { player: PlayerId, hash: String, committed: Vec<PlayerId> },
+    // The player's reveal matched their commitment; `nonce` is passed on so everyone can check it
+    MoveRevealed { player: PlayerId, choice: String, nonce: String },
+    // The idle player was given a move under `TimeoutPolicy::RandomMove`
+    MoveAssigned { player: PlayerId },
+    // The match ended without a result
+    MatchAborted { reason: AbortReason },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ForfeitReason {
+    Timeout,
+    // The player left the room (or lost their connection)
+    Left,
+    // The revealed move and nonce do not hash to the player's commitment
+    RevealMismatch,
+    // The commitment opened to a move outside the rule set
+    InvalidReveal,
+    // The reveal deadline passed before the player revealed
+    RevealTimeout,
+}
+
+impl ForfeitReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ForfeitReason::Timeout => "timeout",
+            ForfeitReason::Left => "left",
+            ForfeitReason::RevealMismatch => "reveal_mismatch",
+            ForfeitReason::InvalidReveal => "invalid_reveal",
+            ForfeitReason::RevealTimeout => "reveal_timeout",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AbortReason {
+    // A round deadline passed under `TimeoutPolicy::EndGame`
+    RoundTimeout,
+    // A round deadline passed and nobody had moved (or every reveal failed)
+    NoMoves,
+    // Every player left the match
+    PlayerLeft,
+}
+
+impl AbortReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AbortReason::RoundTimeout => "round_timeout",
+            AbortReason::NoMoves => "no_moves",
+            AbortReason::PlayerLeft => "player_left",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RematchReason {
+    TieAll,
+    MultipleWinners,
+}
+
+impl RematchReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RematchReason::TieAll => "tie_all",
+            RematchReason::MultipleWinners => "multiple_winners",
+        }
+    }
+}
+
+// Commands rejected by the state machine; the game state is left untouched
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GameError {
+    // `start` while a game is running
+    GameInProgress(RoomPhase),
+    NotEnoughPlayers,
+    InvalidFormat(String),
+    InvalidSettings(String),
+    // `move` outside of the collecting phase
+    MovesNotAccepted(RoomPhase),
+    NotActivePlayer,
+    // Carries the legal moves so the client can be told what to send
+    InvalidChoice(Vec<String>),
+    // A second `move` in a room where moves are final
+    MoveAlreadySubmitted,
+    // `move` or `lock_in` after locking in
+    MoveLocked,
+    // `lock_in` in a room where moves are final
+    LockInDisabled,
+    // `lock_in` before submitting a move
+    NoMoveToLock,
+    // `move` in a commit-reveal room
+    CommitRequired,
+    // `commit` or `reveal` in a room without commit-reveal
+    CommitRevealDisabled,
+    InvalidCommitment,
+    AlreadyCommitted,
+    // `reveal` outside of the reveal phase
+    RevealsNotAccepted(RoomPhase),
+    NoCommitment,
+    AlreadyRevealed,
+    InvalidTransition { from: RoomPhase, to: RoomPhase },
+    // A countdown or deadline fired for a round that is no longer current
+    StaleTimer,
+}
+
+impl GameError {
+    // Stable machine-readable code sent to clients alongside the message
+    pub fn code(&self) -> &'static str {
+        match self {
+            GameError::GameInProgress(_) => "game_in_progress",
+            GameError::NotEnoughPlayers => "not_enough_players",
+            GameError::InvalidFormat(_) => "invalid_format",
+            GameError::InvalidSettings(_) => "invalid_settings",
+            GameError::MovesNotAccepted(RoomPhase::Lobby) => "no_game_in_progress",
+            GameError::MovesNotAccepted(RoomPhase::Finished) => "game_finished",
+            GameError::MovesNotAccepted(RoomPhase::Countdown) => "submissions_not_open",
+            GameError::MovesNotAccepted(_) => "round_resolving",
+            GameError::NotActivePlayer => "not_active_player",
+            GameError::InvalidChoice(_) => "invalid_choice",
+            GameError::MoveAlreadySubmitted => "move_already_submitted",
+            GameError::MoveLocked => "move_locked",
+            GameError::LockInDisabled => "lock_in_disabled",
+            GameError::NoMoveToLock => "no_move_to_lock",
+            GameError::CommitRequired => "commit_required",
+            GameError::CommitRevealDisabled => "commit_reveal_disabled",
+            GameError::InvalidCommitment => "invalid_commitment",
+            GameError::AlreadyCommitted => "already_committed",
+            GameError::RevealsNotAccepted(_) => "reveals_not_open",
+            GameError::NoCommitment => "no_commitment",
+            GameError::AlreadyRevealed => "already_revealed",
+            GameError::InvalidTransition { .. } => "invalid_transition",
+            GameError::StaleTimer => "stale_timer",
+        }
+    }
+}
+
+impl fmt::Display for GameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GameError::GameInProgress(phase) => write!(f, "Game already active (phase: {})", phase),
+            GameError::NotEnoughPlayers => write!(f, "Need at least {} players to start", MIN_PLAYERS),
+            GameError::InvalidFormat(reason) => write!(f, "Invalid match format: {}", reason),
+            GameError::InvalidSettings(reason) => write!(f, "Invalid settings: {}", reason),
+            GameError::MovesNotAccepted(RoomPhase::Lobby) => write!(f, "No game in progress"),
+            GameError::MovesNotAccepted(RoomPhase::Finished) => write!(f, "Game is finished, start a new one"),
+            GameError::MovesNotAccepted(RoomPhase::Countdown) => write!(f, "Submissions are not open yet"),
+            GameError::MovesNotAccepted(phase) => write!(f, "Moves are not accepted during the {} phase", phase),
+            GameError::NotActivePlayer => write!(f, "You are not active in this round"),
+            GameError::InvalidChoice(choices) => write!(f, "Invalid choice, use {}", choices.join("|")),
+            GameError::MoveAlreadySubmitted => write!(f, "You already moved this round and moves are final"),
+            GameError::MoveLocked => write!(f, "Your move is locked in"),
+            GameError::LockInDisabled => write!(f, "Moves are final in this room, there is nothing to lock in"),
+            GameError::NoMoveToLock => write!(f, "Submit a move before locking in"),
+            GameError::CommitRequired => write!(f, "This room uses commit-reveal, send a commit instead of a move"),
+            GameError::CommitRevealDisabled => write!(f, "This room does not use commit-reveal, send a move instead"),
+            GameError::InvalidCommitment => write!(f, "Commitment must be a hex-encoded SHA-256 digest ({} characters)", commit::COMMITMENT_LEN),
+            GameError::AlreadyCommitted => write!(f, "You already committed this round"),
+            GameError::RevealsNotAccepted(phase) => write!(f, "Reveals are not accepted during the {} phase", phase),
+            GameError::NoCommitment => write!(f, "You have no commitment to reveal this round"),
+            GameError::AlreadyRevealed => write!(f, "You already revealed this round"),
+            GameError::InvalidTransition { from, to } => write!(f, "Cannot move from {} to {}", from, to),
+            GameError::StaleTimer => write!(f, "Round is no longer current"),
+        }
+    }
+}
+
+impl std::error::Error for GameError {}
+
+// Outcome type for a completed round among active players
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+    Tie,
+    MultiWinners { winners: Vec<PlayerId> },
+    SingleWinner { winner: PlayerId },
+}
+
+// Compute outcome for current active players: the players whose move beats every other
+// move played win, otherwise (all the same, or no move dominates) the round is a tie
+pub fn compute_round_outcome(rules: &RuleSet, active_players: &HashSet<PlayerId>, moves: &HashMap<PlayerId, String>) -> Outcome {
+    // Consider only active players' moves
+    let mut unique: HashSet<&str> = HashSet::new();
+    for pid in active_players {
+        if let Some(mv) = moves.get(pid) { unique.insert(mv.as_str()); }
+    }
+
+    let Some(win) = rules.dominant_move(&unique) else {
+        return Outcome::Tie;
+    };
+    let mut winners: Vec<PlayerId> = vec![];
+    for pid in active_players {
+        if let Some(mv) = moves.get(pid) { if mv == win { winners.push(*pid); } }
+    }
+    if winners.len() == 1 {
+        Outcome::SingleWinner { winner: winners[0] }
+    } else {
+        Outcome::MultiWinners { winners }
+    }
+}
+
+// A match of one or more games; in each game players submit moves until one winner remains
+#[derive(Debug, Default)]
+pub struct Game {
+    phase: RoomPhase,
+    rules: RuleSet,
+    settings: GameSettings,
+    // Increments every time a round opens, so late deadlines can be told apart
+    round: u32,
+    format: MatchFormat,
+    mode: ScoringMode,
+    // Everyone who started the match; each new game begins with all of them
+    match_players: Vec<PlayerId>,
+    scoreboard: Scoreboard,
+    // Stores each player's submitted (in commit-reveal rooms: revealed) move for the current round
+    moves: HashMap<PlayerId, String>,
+    // Commit-reveal rooms: each player's commitment for the current round
+    commitments: HashMap<PlayerId, String>,
+    // Players whose move can no longer change this round (`MoveChangePolicy::UntilLockIn`)
+    locked: HashSet<PlayerId>,
+    // Current active participants expected to play this round
+    active_players: HashSet<PlayerId>,
+}
+
+impl Game {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_rules(rules: RuleSet, settings: GameSettings) -> Self {
+        Game { rules, settings, ..Self::default() }
+    }
+
+    pub fn rules(&self) -> &RuleSet {
+        &self.rules
+    }
+
+    pub fn settings(&self) -> &GameSettings {
+        &self.settings
+    }
+
+    pub fn round(&self) -> u32 {
+        self.round
+    }
+
+    pub fn phase(&self) -> RoomPhase {
+        self.phase
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.phase.is_in_game()
+    }
+
+    pub fn active_players(&self) -> &HashSet<PlayerId> {
+        &self.active_players
+    }
+
+    // Active players who have moved (or committed) this round, in id order
+    pub fn submitted_players(&self) -> Vec<PlayerId> {
+        let mut submitted: Vec<PlayerId> = self.active_players.iter().filter(|id| self.has_submitted(id)).cloned().collect();
+        submitted.sort();
+        submitted
+    }
+
+    // The player's move in the current round, if they have made (or revealed) one
+    pub fn move_of(&self, player: &PlayerId) -> Option<&str> {
+        self.moves.get(player).map(String::as_str)
+    }
+
+    pub fn match_players(&self) -> &[PlayerId] {
+        &self.match_players
+    }
+
+    pub fn format(&self) -> MatchFormat {
+        self.format
+    }
+
+    pub fn mode(&self) -> ScoringMode {
+        self.mode
+    }
+
+    pub fn scoreboard(&self) -> &Scoreboard {
+        &self.scoreboard
+    }
+
+    pub fn apply(&mut self, command: Command) -> Result<Vec<Event>, GameError> {
+        match command {
+            Command::Start { players, format, mode } => self.start(players, format, mode),
+            Command::Move { player, choice } => self.submit_move(player, choice),
+            Command::LockIn { player } => self.lock_in(player),
+            Command::Commit { player, hash } => self.commit(player, hash),
+            Command::Reveal { player, choice, nonce } => self.reveal(player, choice, nonce),
+            Command::UpdateSettings { settings } => self.update_settings(settings),
+            Command::Leave { player } => self.leave(player),
+            Command::OpenSubmissions { round } => self.open_submissions(round),
+            Command::RoundTimeout { round, entropy } => self.round_timeout(round, entropy),
+        }
+    }
+
+    // End the current match without a result and return to the lobby
+    pub fn abort(&mut self, reason: AbortReason) -> Vec<Event> {
+        self.moves.clear();
+        self.commitments.clear();
+        self.locked.clear();
+        self.active_players.clear();
+        match self.transition(RoomPhase::Lobby) {
+            Ok(event) => vec![Event::MatchAborted { reason }, event],
+            Err(_) => vec![],
+        }
+    }
+
+    // Move to `next`, rejecting transitions the phase graph does not allow
+    fn transition(&mut self, next: RoomPhase) -> Result<Event, GameError> {
+        if !self.phase.can_transition_to(next) {
+            return Err(GameError::InvalidTransition { from: self.phase, to: next });
+        }
+        let from = std::mem::replace(&mut self.phase, next);
+        Ok(Event::PhaseChanged { from, to: next })
+    }
+
+    fn start(&mut self, players: Vec<PlayerId>, format: MatchFormat, mode: ScoringMode) -> Result<Vec<Event>, GameError> {
+        if self.phase.is_in_game() {
+            return Err(GameError::GameInProgress(self.phase));
+        }
+        let mut players = players;
+        players.sort();
+        players.dedup();
+        if players.len() < MIN_PLAYERS {
+            return Err(GameError::NotEnoughPlayers);
+        }
+        format.validate().map_err(GameError::InvalidFormat)?;
+        mode.validate(&format).map_err(GameError::InvalidFormat)?;
+        if !self.phase.can_transition_to(self.round_entry_phase()) {
+            return Err(GameError::InvalidTransition { from: self.phase, to: self.round_entry_phase() });
+        }
+        self.format = format;
+        self.mode = mode;
+        self.scoreboard = Scoreboard::new(&players);
+        self.match_players = players;
+        let mut events = vec![self.begin_game()];
+        self.open_round(&mut events)?;
+        Ok(events)
+    }
+
+    // Rounds open with a countdown when the room has one, otherwise straight into collecting moves
+    fn round_entry_phase(&self) -> RoomPhase {
+        if self.settings.countdown_secs.is_some() { RoomPhase::Countdown } else { RoomPhase::Collecting }
+    }
+
+    // Begin a fresh round
+    fn open_round(&mut self, events: &mut Vec<Event>) -> Result<(), GameError> {
+        events.push(self.transition(self.round_entry_phase())?);
+        self.round += 1;
+        self.commitments.clear();
+        self.locked.clear();
+        match self.settings.countdown_secs {
+            Some(secs) => events.push(Event::CountdownStarted { round: self.round, secs }),
+            None => events.push(Event::RoundOpened { round: self.round }),
+        }
+        Ok(())
+    }
+
+    // Countdown -> Collecting
+    fn open_submissions(&mut self, round: u32) -> Result<Vec<Event>, GameError> {
+        if self.phase != RoomPhase::Countdown || round != self.round {
+            return Err(GameError::StaleTimer);
+        }
+        Ok(vec![self.transition(RoomPhase::Collecting)?, Event::RoundOpened { round }])
+    }
+
+    // Reset the round state for the next game of the match
+    fn begin_game(&mut self) -> Event {
+        self.moves.clear();
+        self.active_players = self.match_players.iter().cloned().collect();
+        Event::GameStarted {
+            players: self.match_players.clone(),
+            rules: self.rules.name().to_string(),
+            choices: self.rules.moves().to_vec(),
+            format: self.format,
+            mode: self.mode,
+            game_number: self.scoreboard.games_played() + 1,
+        }
+    }
+
+    fn submit_move(&mut self, player: PlayerId, choice: String) -> Result<Vec<Event>, GameError> {
+        if self.settings.commit_reveal {
+            return Err(GameError::CommitRequired);
+        }
+        if self.phase != RoomPhase::Collecting {
+            return Err(GameError::MovesNotAccepted(self.phase));
+        }
+        if !self.active_players.contains(&player) {
+            return Err(GameError::NotActivePlayer);
+        }
+        if !self.rules.is_valid_move(&choice) {
+            return Err(GameError::InvalidChoice(self.rules.moves().to_vec()));
+        }
+        if self.moves.contains_key(&player) {
+            if !self.settings.allows_move_changes() {
+                return Err(GameError::MoveAlreadySubmitted);
+            }
+            if self.locked.contains(&player) {
+                return Err(GameError::MoveLocked);
+            }
+        }
+        let first = self.moves.insert(player, choice).is_none();
+        let mut events = vec![Event::MoveAccepted { player, round: self.round }];
+        if first {
+            events.extend(self.progress());
+        }
+        if self.round_complete() {
+            events.extend(self.resolve_round()?);
+        }
+        Ok(events)
+    }
+
+    fn lock_in(&mut self, player: PlayerId) -> Result<Vec<Event>, GameError> {
+        if !self.settings.allows_move_changes() {
+            return Err(GameError::LockInDisabled);
+        }
+        if self.phase != RoomPhase::Collecting {
+            return Err(GameError::MovesNotAccepted(self.phase));
+        }
+        if !self.active_players.contains(&player) {
+            return Err(GameError::NotActivePlayer);
+        }
+        if !self.moves.contains_key(&player) {
+            return Err(GameError::NoMoveToLock);
+        }
+        if !self.locked.insert(player) {
+            return Err(GameError::MoveLocked);
+        }
+        let mut locked: Vec<PlayerId> = self.locked.iter().cloned().collect();
+        locked.sort();
+        let mut events = vec![Event::MoveLocked { player, locked }];
+        if self.round_complete() {
+            events.extend(self.resolve_round()?);
+        }
+        Ok(events)
+    }
+
+    // Whether the round can be closed before its deadline: every active player has moved (or locked in,
+    // when moves can change, or committed in commit-reveal rooms); countdown rounds always wait for the window to close
+    fn round_complete(&self) -> bool {
+        if self.settings.reveal_at_deadline() {
+            return false;
+        }
+        if self.settings.commit_reveal {
+            self.active_players.iter().all(|id| self.commitments.contains_key(id))
+        } else if self.settings.allows_move_changes() {
+            self.active_players.iter().all(|id| self.locked.contains(id))
+        } else {
+            self.moves.len() == self.active_players.len()
+        }
+    }
+
+    fn commit(&mut self, player: PlayerId, hash: String) -> Result<Vec<Event>, GameError> {
+        if !self.settings.commit_reveal {
+            return Err(GameError::CommitRevealDisabled);
+        }
+        if self.phase != RoomPhase::Collecting {
+            return Err(GameError::MovesNotAccepted(self.phase));
+        }
+        if !self.active_players.contains(&player) {
+            return Err(GameError::NotActivePlayer);
+        }
+        let hash = commit::normalize_commitment(&hash).ok_or(GameError::InvalidCommitment)?;
+        if self.commitments.contains_key(&player) {
+            return Err(GameError::AlreadyCommitted);
+        }
+        self.commitments.insert(player, hash.clone());
+        let mut events = vec![Event::MoveAccepted { player, round: self.round }];
+        // Countdown rooms publish the commitments when the window closes, so their order stays hidden
+        if !self.settings.reveal_at_deadline() {
+            events.push(Event::MoveCommitted { player, hash, committed: self.committed_players() });
+        }
+        events.extend(self.progress());
+        // Once everyone has committed, ask for the reveals
+        if self.round_complete() {
+            events.extend(self.open_reveals()?);
+        }
+        Ok(events)
+    }
+
+    // A reveal that does not open the player's commitment to a legal move forfeits the player
+    fn reveal(&mut self, player: PlayerId, choice: String, nonce: String) -> Result<Vec<Event>, GameError> {
+        if !self.settings.commit_reveal {
+            return Err(GameError::CommitRevealDisabled);
+        }
+        if self.phase != RoomPhase::Reveal {
+            return Err(GameError::RevealsNotAccepted(self.phase));
+        }
+        if !self.active_players.contains(&player) {
+            return Err(GameError::NotActivePlayer);
+        }
+        let Some(commitment) = self.commitments.get(&player) else {
+            return Err(GameError::NoCommitment);
+        };
+        if self.moves.contains_key(&player) {
+            return Err(GameError::AlreadyRevealed);
+        }
+        let event = if !commit::verify(commitment, &choice, &nonce) {
+            self.forfeit(player, ForfeitReason::RevealMismatch)
+        } else if !self.rules.is_valid_move(&choice) {
+            self.forfeit(player, ForfeitReason::InvalidReveal)
+        } else {
+            self.moves.insert(player, choice.clone());
+            Event::MoveRevealed { player, choice, nonce }
+        };
+        let mut events = vec![event];
+        if self.awaiting_reveal().is_empty() {
+            events.extend(self.finish_reveals()?);
+        }
+        Ok(events)
+    }
+
+    // Split the active players into those who have moved (or committed) this round and those still awaited.
+    // Countdown rooms reveal everything at once when the window closes, so they send no progress that would show who was slowest.
+    fn progress(&self) -> Option<Event> {
+        if self.settings.reveal_at_deadline() {
+            return None;
+        }
+        let submitted = self.submitted_players();
+        let mut waiting: Vec<PlayerId> = self.active_players.iter().filter(|id| !self.has_submitted(id)).cloned().collect();
+        waiting.sort();
+        Some(Event::RoundProgress { round: self.round, submitted, waiting })
+    }
+
+    // Players who have committed this round, in id order
+    fn committed_players(&self) -> Vec<PlayerId> {
+        let mut committed: Vec<PlayerId> = self.commitments.keys().cloned().collect();
+        committed.sort();
+        committed
+    }
+
+    // Committed players who have not revealed yet
+    fn awaiting_reveal(&self) -> Vec<PlayerId> {
+        let mut waiting: Vec<PlayerId> = self.commitments.keys().filter(|id| !self.moves.contains_key(id)).cloned().collect();
+        waiting.sort();
+        waiting
+    }
+
+    // Whether the player has moved (or committed to a move) this round
+    fn has_submitted(&self, player: &PlayerId) -> bool {
+        self.moves.contains_key(player) || self.commitments.contains_key(player)
+    }
+
+    // Take the player out of the current round: eliminated in elimination mode, scoring nothing in round robin
+    fn forfeit(&mut self, player: PlayerId, reason: ForfeitReason) -> Event {
+        self.commitments.remove(&player);
+        if self.mode == ScoringMode::Elimination {
+            self.active_players.remove(&player);
+        }
+        Event::PlayerForfeited { player, reason }
+    }
+
+    fn update_settings(&mut self, settings: GameSettings) -> Result<Vec<Event>, GameError> {
+        if self.phase.is_in_game() {
+            return Err(GameError::GameInProgress(self.phase));
+        }
+        settings.validate().map_err(GameError::InvalidSettings)?;
+        self.settings = settings.clone();
+        Ok(vec![Event::SettingsChanged { settings }])
+    }
+
+    // A player left the room: drop them from the match, and settle the round if it was only waiting for them.
+    // A lone remaining player wins the game, and the match too if nobody else is left to play it.
+    fn leave(&mut self, player: PlayerId) -> Result<Vec<Event>, GameError> {
+        self.match_players.retain(|id| *id != player);
+        if !self.phase.is_in_game() || !self.active_players.contains(&player) {
+            return Ok(vec![]);
+        }
+        self.moves.remove(&player);
+        self.commitments.remove(&player);
+        self.locked.remove(&player);
+        self.active_players.remove(&player);
+        let mut events = vec![Event::PlayerForfeited { player, reason: ForfeitReason::Left }];
+        match self.active_players.len() {
+            0 => events.extend(self.abort(AbortReason::PlayerLeft)),
+            1 => events.extend(self.resolve_round()?),
+            _ => match self.phase {
+                RoomPhase::Collecting if self.round_complete() => events.extend(self.close_submissions()?),
+                RoomPhase::Reveal if self.awaiting_reveal().is_empty() => events.extend(self.finish_reveals()?),
+                _ => {}
+            },
+        }
+        Ok(events)
+    }
+
+    // Apply the room's timeout policy to the players who have not moved, then close the round.
+    // In the reveal phase of a commit-reveal room everyone who has not revealed forfeits, whatever the policy.
+    fn round_timeout(&mut self, round: u32, entropy: u64) -> Result<Vec<Event>, GameError> {
+        if round != self.round {
+            return Err(GameError::StaleTimer);
+        }
+        match self.phase {
+            RoomPhase::Collecting => {}
+            RoomPhase::Reveal if self.settings.commit_reveal => return self.reveal_timeout(),
+            _ => return Err(GameError::StaleTimer),
+        }
+        let mut idle: Vec<PlayerId> = self.active_players.iter().filter(|id| !self.has_submitted(id)).cloned().collect();
+        idle.sort();
+        if idle.is_empty() {
+            return self.close_submissions();
+        }
+        let policy = self.settings.timeout_policy;
+        if policy == TimeoutPolicy::EndGame {
+            return Ok(self.abort(AbortReason::RoundTimeout));
+        }
+        if policy == TimeoutPolicy::Forfeit && self.moves.is_empty() && self.commitments.is_empty() {
+            return Ok(self.abort(AbortReason::NoMoves));
+        }
+        let mut events = vec![];
+        let mut seed = entropy;
+        for player in idle {
+            if policy == TimeoutPolicy::RandomMove {
+                // In commit-reveal rooms an assigned move counts as already revealed
+                let pick = (next_random(&mut seed) % self.rules.moves().len() as u64) as usize;
+                self.moves.insert(player, self.rules.moves()[pick].clone());
+                events.push(Event::MoveAssigned { player });
+            } else {
+                events.push(self.forfeit(player, ForfeitReason::Timeout));
+            }
+        }
+        events.extend(self.close_submissions()?);
+        Ok(events)
+    }
+
+    fn reveal_timeout(&mut self) -> Result<Vec<Event>, GameError> {
+        let mut events: Vec<Event> = self.awaiting_reveal().into_iter().map(|player| self.forfeit(player, ForfeitReason::RevealTimeout)).collect();
+        events.extend(self.finish_reveals()?);
+        Ok(events)
+    }
+
+    // Moves are closed: resolve the round, or in commit-reveal rooms ask for the reveals first
+    fn close_submissions(&mut self) -> Result<Vec<Event>, GameError> {
+        if self.settings.commit_reveal { self.open_reveals() } else { self.resolve_round() }
+    }
+
+    // Collecting -> Reveal in a commit-reveal room; the round stays there until every commitment is opened
+    fn open_reveals(&mut self) -> Result<Vec<Event>, GameError> {
+        let mut events = vec![];
+        if self.settings.reveal_at_deadline() {
+            // The commitments held back while the window was open, in id order
+            let committed = self.committed_players();
+            events.extend(committed.iter().map(|player| Event::MoveCommitted { player: *player, hash: self.commitments[player].clone(), committed: committed.clone() }));
+        }
+        events.push(self.transition(RoomPhase::Reveal)?);
+        if self.awaiting_reveal().is_empty() {
+            // Only assigned moves, nothing to reveal
+            events.extend(self.finish_reveals()?);
+        } else {
+            events.push(Event::RevealOpened { round: self.round });
+        }
+        Ok(events)
+    }
+
+    // Every commitment is settled: resolve the round, unless no valid move is left
+    fn finish_reveals(&mut self) -> Result<Vec<Event>, GameError> {
+        if self.moves.is_empty() {
+            return Ok(self.abort(AbortReason::NoMoves));
+        }
+        self.resolve_round()
+    }
+
+    // Collecting (or Countdown, when forfeits leave a lone player) -> Reveal (commit-reveal rooms are
+    // already there), then either back to Collecting for a rematch or the next game, or Finished
+    fn resolve_round(&mut self) -> Result<Vec<Event>, GameError> {
+        let mut events = vec![];
+        if self.phase != RoomPhase::Reveal {
+            events.push(self.transition(RoomPhase::Reveal)?);
+        }
+        match self.mode {
+            ScoringMode::Elimination => self.resolve_elimination(&mut events)?,
+            ScoringMode::RoundRobin { .. } => self.resolve_round_robin(&mut events)?,
+        }
+        Ok(events)
+    }
+
+    fn resolve_elimination(&mut self, events: &mut Vec<Event>) -> Result<(), GameError> {
+        // A lone survivor (everyone else forfeited) wins outright
+        let outcome = if self.active_players.len() == 1 {
+            Outcome::SingleWinner { winner: *self.active_players.iter().next().unwrap() }
+        } else {
+            compute_round_outcome(&self.rules, &self.active_players, &self.moves)
+        };
+        let moves = std::mem::take(&mut self.moves);
+        match outcome {
+            // Rematch with same active players
+            Outcome::Tie => {
+                events.push(Event::Rematch { next_players: self.active_players.iter().cloned().collect(), reason: RematchReason::TieAll, moves });
+                self.open_round(events)?;
+            }
+            Outcome::MultiWinners { winners } => {
+                // Only winners continue
+                self.active_players = winners.iter().cloned().collect();
+                events.push(Event::Rematch { next_players: winners, reason: RematchReason::MultipleWinners, moves });
+                self.open_round(events)?;
+            }
+            Outcome::SingleWinner { winner } => {
+                // End game and update the match standings
+                events.push(Event::RoundResult { winner, moves });
+                self.scoreboard.record_win(winner);
+                let champion = self.scoreboard.match_winner(&self.format, &self.match_players);
+                self.end_game(champion, events)?;
+            }
+        }
+        Ok(())
+    }
+
+    // Nobody is eliminated: score the round pairwise and play the next one until the rounds run out
+    fn resolve_round_robin(&mut self, events: &mut Vec<Event>) -> Result<(), GameError> {
+        let ScoringMode::RoundRobin { rounds } = self.mode else { return Ok(()) };
+        let moves = std::mem::take(&mut self.moves);
+        let mut table = score_pairwise(&self.rules, &self.match_players, &moves);
+        self.scoreboard.record_round(&mut table);
+        events.push(Event::RoundScored { round: self.scoreboard.games_played(), moves, table });
+        let champion = self.scoreboard.leader_after(rounds, &self.match_players);
+        self.end_game(champion, events)
+    }
+
+    // Publish the standings, then either finish the match or begin its next game
+    fn end_game(&mut self, champion: Option<PlayerId>, events: &mut Vec<Event>) -> Result<(), GameError> {
+        events.push(Event::ScoreUpdate { scores: self.scoreboard.scores().clone(), games_played: self.scoreboard.games_played() });
+        let mut champion = champion;
+        if champion.is_none() && self.match_players.len() < MIN_PLAYERS {
+            // Everyone else left: the last player standing takes the match
+            match self.match_players.first() {
+                Some(last) => champion = Some(*last),
+                None => {
+                    events.extend(self.abort(AbortReason::PlayerLeft));
+                    return Ok(());
+                }
+            }
+        }
+        if let Some(champion) = champion {
+            self.active_players.clear();
+            events.push(Event::MatchResult { winner: champion, scores: self.scoreboard.scores().clone() });
+            events.push(self.transition(RoomPhase::Finished)?);
+        } else {
+            events.push(self.begin_game());
+            self.open_round(events)?;
+        }
+        Ok(())
+    }
+}
+
+// splitmix64: spreads one caller-supplied seed over several picks without pulling in an RNG crate
+fn next_random(seed: &mut u64) -> u64 {
+    *seed = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = *seed;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::settings::MoveChangePolicy;
+
+    fn players(n: u128) -> Vec<PlayerId> {
+        (1..=n).map(Uuid::from_u128).collect()
+    }
+
+    fn moves(played: &[(PlayerId, &str)]) -> HashMap<PlayerId, String> {
+        played.iter().map(|(id, mv)| (*id, mv.to_string())).collect()
+    }
+
+    // A single elimination game between `n` players, collecting moves for round 1
+    fn started(settings: GameSettings, n: u128) -> (Game, Vec<PlayerId>) {
+        let mut game = Game::with_rules(RuleSet::classic(), settings);
+        let players = players(n);
+        game.apply(Command::Start { players: players.clone(), format: MatchFormat::Single, mode: ScoringMode::Elimination }).unwrap();
+        (game, players)
+    }
+
+    fn timeout_after(policy: TimeoutPolicy) -> GameSettings {
+        GameSettings { round_timeout_secs: Some(10), timeout_policy: policy, ..GameSettings::default() }
+    }
+
+    #[test]
+    fn outcome_is_a_tie_when_everyone_plays_the_same_or_every_move_is_played() {
+        let rules = RuleSet::classic();
+        let [a, b, c] = players(3)[..] else { unreachable!() };
+        let active: HashSet<PlayerId> = [a, b, c].into();
+        assert_eq!(compute_round_outcome(&rules, &active, &moves(&[(a, "rock"), (b, "rock"), (c, "rock")])), Outcome::Tie);
+        assert_eq!(compute_round_outcome(&rules, &active, &moves(&[(a, "rock"), (b, "paper"), (c, "scissors")])), Outcome::Tie);
+    }
+
+    #[test]
+    fn outcome_has_multiple_winners_when_several_players_share_the_winning_move() {
+        let rules = RuleSet::classic();
+        let [a, b, c] = players(3)[..] else { unreachable!() };
+        let active: HashSet<PlayerId> = [a, b, c].into();
+        let Outcome::MultiWinners { mut winners } = compute_round_outcome(&rules, &active, &moves(&[(a, "paper"), (b, "paper"), (c, "rock")])) else {
+            panic!("expected multiple winners");
+        };
+        winners.sort();
+        assert_eq!(winners, vec![a, b]);
+    }
+
+    #[test]
+    fn outcome_has_a_single_winner_and_ignores_inactive_players() {
+        let rules = RuleSet::classic();
+        let [a, b, c] = players(3)[..] else { unreachable!() };
+        let active: HashSet<PlayerId> = [a, b].into();
+        let played = moves(&[(a, "scissors"), (b, "paper"), (c, "scissors")]);
+        assert_eq!(compute_round_outcome(&rules, &active, &played), Outcome::SingleWinner { winner: a });
+    }
+
+    #[test]
+    fn start_needs_enough_distinct_players_and_no_running_game() {
+        let mut game = Game::new();
+        let [a, b] = players(2)[..] else { unreachable!() };
+        let start = |players: Vec<PlayerId>| Command::Start { players, format: MatchFormat::Single, mode: ScoringMode::Elimination };
+        assert_eq!(game.apply(start(vec![a, a])), Err(GameError::NotEnoughPlayers));
+        assert_eq!(game.phase(), RoomPhase::Lobby);
+        let events = game.apply(start(vec![b, a])).unwrap();
+        assert!(matches!(&events[0], Event::GameStarted { players, game_number: 1, .. } if *players == vec![a, b]));
+        assert_eq!(events[1], Event::PhaseChanged { from: RoomPhase::Lobby, to: RoomPhase::Collecting });
+        assert_eq!(events[2], Event::RoundOpened { round: 1 });
+        assert_eq!(game.apply(start(vec![a, b])), Err(GameError::GameInProgress(RoomPhase::Collecting)));
+    }
+
+    #[test]
+    fn moves_are_checked_against_the_game_and_the_rules() {
+        let mut game = Game::new();
+        let [a, b, c] = players(3)[..] else { unreachable!() };
+        assert_eq!(game.apply(Command::Move { player: a, choice: "rock".into() }), Err(GameError::MovesNotAccepted(RoomPhase::Lobby)));
+        game.apply(Command::Start { players: vec![a, b], format: MatchFormat::Single, mode: ScoringMode::Elimination }).unwrap();
+        assert_eq!(game.apply(Command::Move { player: c, choice: "rock".into() }), Err(GameError::NotActivePlayer));
+        assert_eq!(game.apply(Command::Move { player: a, choice: "lizard".into() }), Err(GameError::InvalidChoice(game.rules().moves().to_vec())));
+        game.apply(Command::Move { player: a, choice: "rock".into() }).unwrap();
+        assert_eq!(game.apply(Command::Move { player: a, choice: "paper".into() }), Err(GameError::MoveAlreadySubmitted));
+        assert_eq!(game.move_of(&a), Some("rock"));
+    }
+
+    #[test]
+    fn a_tie_replays_the_round_with_everyone() {
+        let (mut game, p) = started(GameSettings::default(), 2);
+        game.apply(Command::Move { player: p[0], choice: "rock".into() }).unwrap();
+        let events = game.apply(Command::Move { player: p[1], choice: "rock".into() }).unwrap();
+        assert!(events.iter().any(|event| matches!(event, Event::Rematch { reason: RematchReason::TieAll, next_players, .. } if next_players.len() == 2)));
+        assert_eq!(events.last(), Some(&Event::RoundOpened { round: 2 }));
+        assert_eq!(game.phase(), RoomPhase::Collecting);
+        assert_eq!(game.active_players().len(), 2);
+        assert_eq!(game.move_of(&p[0]), None);
+    }
+
+    #[test]
+    fn only_the_winners_play_on_after_a_shared_win() {
+        let (mut game, p) = started(GameSettings::default(), 3);
+        game.apply(Command::Move { player: p[0], choice: "paper".into() }).unwrap();
+        game.apply(Command::Move { player: p[1], choice: "paper".into() }).unwrap();
+        let events = game.apply(Command::Move { player: p[2], choice: "rock".into() }).unwrap();
+        assert!(events.iter().any(|event| matches!(event, Event::Rematch { reason: RematchReason::MultipleWinners, .. })));
+        assert_eq!(game.active_players(), &HashSet::from([p[0], p[1]]));
+        assert_eq!(game.apply(Command::Move { player: p[2], choice: "rock".into() }), Err(GameError::NotActivePlayer));
+        game.apply(Command::Move { player: p[0], choice: "scissors".into() }).unwrap();
+        let events = game.apply(Command::Move { player: p[1], choice: "paper".into() }).unwrap();
+        assert!(events.contains(&Event::RoundResult { winner: p[0], moves: moves(&[(p[0], "scissors"), (p[1], "paper")]) }));
+        assert_eq!(game.phase(), RoomPhase::Finished);
+    }
+
+    #[test]
+    fn phase_transitions_follow_the_round_lifecycle() {
+        use RoomPhase::*;
+        assert!(Lobby.can_transition_to(Collecting));
+        assert!(Lobby.can_transition_to(Countdown));
+        assert!(Countdown.can_transition_to(Collecting));
+        assert!(Countdown.can_transition_to(Reveal));
+        assert!(Collecting.can_transition_to(Reveal));
+        assert!(Reveal.can_transition_to(Collecting));
+        assert!(Reveal.can_transition_to(Finished));
+        assert!(Finished.can_transition_to(Collecting));
+        for phase in [Lobby, Countdown, Collecting, Reveal, Finished] {
+            assert!(phase.can_transition_to(Lobby));
+        }
+        assert!(!Lobby.can_transition_to(Reveal));
+        assert!(!Lobby.can_transition_to(Finished));
+        assert!(!Collecting.can_transition_to(Finished));
+        assert!(!Collecting.can_transition_to(Countdown));
+        assert!(!Finished.can_transition_to(Reveal));
+    }
+
+    #[test]
+    fn leaving_settles_a_round_that_was_only_waiting_for_the_leaver() {
+        let (mut game, p) = started(GameSettings::default(), 3);
+        game.apply(Command::Move { player: p[0], choice: "rock".into() }).unwrap();
+        game.apply(Command::Move { player: p[1], choice: "scissors".into() }).unwrap();
+        let events = game.apply(Command::Leave { player: p[2] }).unwrap();
+        assert_eq!(events[0], Event::PlayerForfeited { player: p[2], reason: ForfeitReason::Left });
+        assert!(events.contains(&Event::RoundResult { winner: p[0], moves: moves(&[(p[0], "rock"), (p[1], "scissors")]) }));
+        assert_eq!(game.phase(), RoomPhase::Finished);
+        assert_eq!(game.match_players(), &p[..2]);
+    }
+
+    #[test]
+    fn the_last_player_left_wins_the_game_and_the_match() {
+        let (mut game, p) = started(GameSettings::default(), 2);
+        let events = game.apply(Command::Leave { player: p[1] }).unwrap();
+        assert!(events.contains(&Event::RoundResult { winner: p[0], moves: HashMap::new() }));
+        assert!(events.iter().any(|event| matches!(event, Event::MatchResult { winner, .. } if *winner == p[0])));
+        assert_eq!(game.phase(), RoomPhase::Finished);
+    }
+
+    #[test]
+    fn a_player_who_left_cannot_win_a_round_robin_match() {
+        let mut game = Game::with_rules(RuleSet::classic(), GameSettings::default());
+        let p = players(3);
+        game.apply(Command::Start { players: p.clone(), format: MatchFormat::Single, mode: ScoringMode::RoundRobin { rounds: 2 } }).unwrap();
+        game.apply(Command::Move { player: p[0], choice: "rock".into() }).unwrap();
+        game.apply(Command::Move { player: p[1], choice: "scissors".into() }).unwrap();
+        game.apply(Command::Move { player: p[2], choice: "scissors".into() }).unwrap();
+        game.apply(Command::Leave { player: p[0] }).unwrap();
+        game.apply(Command::Move { player: p[1], choice: "rock".into() }).unwrap();
+        let events = game.apply(Command::Move { player: p[2], choice: "scissors".into() }).unwrap();
+        let scores: HashMap<PlayerId, u32> = [(p[0], 2), (p[1], 1), (p[2], 0)].into();
+        assert!(events.contains(&Event::MatchResult { winner: p[1], scores }), "{:?}", events);
+        assert_eq!(game.phase(), RoomPhase::Finished);
+    }
+
+    #[test]
+    fn leaving_outside_a_game_only_drops_the_player_from_the_match() {
+        let (mut game, p) = started(GameSettings::default(), 2);
+        game.apply(Command::Leave { player: p[1] }).unwrap();
+        assert_eq!(game.apply(Command::Leave { player: p[0] }).unwrap(), vec![]);
+        assert!(game.match_players().is_empty());
+        assert_eq!(game.phase(), RoomPhase::Finished);
+    }
+
+    #[test]
+    fn forfeit_timeout_resolves_the_round_among_players_who_moved() {
+        let (mut game, p) = started(timeout_after(TimeoutPolicy::Forfeit), 3);
+        game.apply(Command::Move { player: p[0], choice: "paper".into() }).unwrap();
+        let events = game.apply(Command::RoundTimeout { round: 1, entropy: 7 }).unwrap();
+        assert_eq!(events[0], Event::PlayerForfeited { player: p[1], reason: ForfeitReason::Timeout });
+        assert_eq!(events[1], Event::PlayerForfeited { player: p[2], reason: ForfeitReason::Timeout });
+        assert!(events.contains(&Event::RoundResult { winner: p[0], moves: moves(&[(p[0], "paper")]) }));
+        assert_eq!(game.phase(), RoomPhase::Finished);
+    }
+
+    #[test]
+    fn forfeit_timeout_without_any_move_aborts_the_match() {
+        let (mut game, _) = started(timeout_after(TimeoutPolicy::Forfeit), 2);
+        let events = game.apply(Command::RoundTimeout { round: 1, entropy: 7 }).unwrap();
+        assert_eq!(events[0], Event::MatchAborted { reason: AbortReason::NoMoves });
+        assert_eq!(game.phase(), RoomPhase::Lobby);
+    }
+
+    #[test]
+    fn random_move_timeout_assigns_legal_moves_to_idle_players() {
+        let (mut game, p) = started(timeout_after(TimeoutPolicy::RandomMove), 2);
+        game.apply(Command::Move { player: p[0], choice: "rock".into() }).unwrap();
+        let events = game.apply(Command::RoundTimeout { round: 1, entropy: 7 }).unwrap();
+        assert_eq!(events[0], Event::MoveAssigned { player: p[1] });
+        let moves = events.iter().find_map(|event| match event {
+            Event::RoundResult { moves, .. } | Event::Rematch { moves, .. } => Some(moves),
+            _ => None,
+        });
+        let assigned = moves.expect("the round is resolved").get(&p[1]).expect("the idle player has a move");
+        assert!(game.rules().is_valid_move(assigned));
+    }
+
+    #[test]
+    fn end_game_timeout_aborts_the_match() {
+        let (mut game, p) = started(timeout_after(TimeoutPolicy::EndGame), 2);
+        game.apply(Command::Move { player: p[0], choice: "rock".into() }).unwrap();
+        let events = game.apply(Command::RoundTimeout { round: 1, entropy: 7 }).unwrap();
+        assert_eq!(events, vec![Event::MatchAborted { reason: AbortReason::RoundTimeout }, Event::PhaseChanged { from: RoomPhase::Collecting, to: RoomPhase::Lobby }]);
+        assert!(game.active_players().is_empty());
+    }
+
+    fn countdown() -> GameSettings {
+        GameSettings { countdown_secs: Some(3), submission_window_secs: Some(2), ..GameSettings::default() }
+    }
+
+    #[test]
+    fn countdown_rounds_refuse_moves_until_submissions_open() {
+        let mut game = Game::with_rules(RuleSet::classic(), countdown());
+        let p = players(2);
+        let events = game.apply(Command::Start { players: p.clone(), format: MatchFormat::Single, mode: ScoringMode::Elimination }).unwrap();
+        assert!(events.contains(&Event::CountdownStarted { round: 1, secs: 3 }));
+        assert_eq!(game.phase(), RoomPhase::Countdown);
+        let err = game.apply(Command::Move { player: p[0], choice: "rock".into() }).unwrap_err();
+        assert_eq!(err.code(), "submissions_not_open");
+        assert_eq!(game.apply(Command::OpenSubmissions { round: 2 }).unwrap_err(), GameError::StaleTimer);
+        let events = game.apply(Command::OpenSubmissions { round: 1 }).unwrap();
+        assert_eq!(events, vec![Event::PhaseChanged { from: RoomPhase::Countdown, to: RoomPhase::Collecting }, Event::RoundOpened { round: 1 }]);
+        game.apply(Command::Move { player: p[0], choice: "rock".into() }).unwrap();
+    }
+
+    #[test]
+    fn countdown_rounds_reveal_only_when_the_window_closes() {
+        let mut game = Game::with_rules(RuleSet::classic(), countdown());
+        let p = players(2);
+        game.apply(Command::Start { players: p.clone(), format: MatchFormat::Single, mode: ScoringMode::Elimination }).unwrap();
+        game.apply(Command::OpenSubmissions { round: 1 }).unwrap();
+        game.apply(Command::Move { player: p[0], choice: "rock".into() }).unwrap();
+        let events = game.apply(Command::Move { player: p[1], choice: "scissors".into() }).unwrap();
+        assert!(!events.iter().any(|event| matches!(event, Event::RoundResult { .. } | Event::PhaseChanged { .. })), "{:?}", events);
+        assert_eq!(game.phase(), RoomPhase::Collecting);
+        let events = game.apply(Command::RoundTimeout { round: 1, entropy: 7 }).unwrap();
+        assert!(events.contains(&Event::RoundResult { winner: p[0], moves: moves(&[(p[0], "rock"), (p[1], "scissors")]) }));
+        assert_eq!(game.phase(), RoomPhase::Finished);
+    }
+
+    fn commit_reveal() -> GameSettings {
+        GameSettings { commit_reveal: true, round_timeout_secs: Some(10), ..GameSettings::default() }
+    }
+
+    fn commit(game: &mut Game, player: PlayerId, choice: &str, nonce: &str) -> Vec<Event> {
+        game.apply(Command::Commit { player, hash: commit::commitment_hash(choice, nonce) }).unwrap()
+    }
+
+    fn reveal(game: &mut Game, player: PlayerId, choice: &str, nonce: &str) -> Result<Vec<Event>, GameError> {
+        game.apply(Command::Reveal { player, choice: choice.into(), nonce: nonce.into() })
+    }
+
+    #[test]
+    fn commit_reveal_rooms_take_commitments_instead_of_moves() {
+        let (mut game, p) = started(commit_reveal(), 2);
+        assert_eq!(game.apply(Command::Move { player: p[0], choice: "rock".into() }), Err(GameError::CommitRequired));
+        assert_eq!(game.apply(Command::Commit { player: p[0], hash: "rock".into() }), Err(GameError::InvalidCommitment));
+        assert_eq!(reveal(&mut game, p[0], "rock", "n1"), Err(GameError::RevealsNotAccepted(RoomPhase::Collecting)));
+        commit(&mut game, p[0], "rock", "n1");
+        assert_eq!(game.apply(Command::Commit { player: p[0], hash: commit::commitment_hash("paper", "n1") }), Err(GameError::AlreadyCommitted));
+        assert_eq!(game.submitted_players(), vec![p[0]]);
+        assert_eq!(game.move_of(&p[0]), None);
+        let events = commit(&mut game, p[1], "paper", "n2");
+        assert!(events.ends_with(&[Event::PhaseChanged { from: RoomPhase::Collecting, to: RoomPhase::Reveal }, Event::RevealOpened { round: 1 }]), "{:?}", events);
+    }
+
+    #[test]
+    fn reveals_that_do_not_open_the_commitment_to_a_legal_move_forfeit() {
+        let (mut game, p) = started(commit_reveal(), 3);
+        commit(&mut game, p[0], "rock", "n1");
+        commit(&mut game, p[1], "paper", "n2");
+        commit(&mut game, p[2], "lizard", "n3");
+        assert_eq!(reveal(&mut game, p[0], "rock", "n1").unwrap(), vec![Event::MoveRevealed { player: p[0], choice: "rock".into(), nonce: "n1".into() }]);
+        assert_eq!(reveal(&mut game, p[0], "rock", "n1"), Err(GameError::AlreadyRevealed));
+        assert_eq!(reveal(&mut game, p[1], "paper", "n1").unwrap(), vec![Event::PlayerForfeited { player: p[1], reason: ForfeitReason::RevealMismatch }]);
+        let events = reveal(&mut game, p[2], "lizard", "n3").unwrap();
+        assert_eq!(events[0], Event::PlayerForfeited { player: p[2], reason: ForfeitReason::InvalidReveal });
+        assert!(events.contains(&Event::RoundResult { winner: p[0], moves: moves(&[(p[0], "rock")]) }));
+        assert_eq!(game.phase(), RoomPhase::Finished);
+    }
+
+    #[test]
+    fn reveal_deadline_forfeits_players_who_did_not_reveal() {
+        let (mut game, p) = started(commit_reveal(), 3);
+        commit(&mut game, p[0], "rock", "n1");
+        commit(&mut game, p[1], "paper", "n2");
+        commit(&mut game, p[2], "scissors", "n3");
+        reveal(&mut game, p[0], "rock", "n1").unwrap();
+        let events = game.apply(Command::RoundTimeout { round: 1, entropy: 7 }).unwrap();
+        assert_eq!(events[0], Event::PlayerForfeited { player: p[1], reason: ForfeitReason::RevealTimeout });
+        assert_eq!(events[1], Event::PlayerForfeited { player: p[2], reason: ForfeitReason::RevealTimeout });
+        assert!(events.contains(&Event::RoundResult { winner: p[0], moves: moves(&[(p[0], "rock")]) }));
+    }
+
+    #[test]
+    fn a_reveal_phase_without_any_valid_reveal_aborts_the_match() {
+        let (mut game, p) = started(commit_reveal(), 2);
+        commit(&mut game, p[0], "rock", "n1");
+        commit(&mut game, p[1], "paper", "n2");
+        let events = game.apply(Command::RoundTimeout { round: 1, entropy: 7 }).unwrap();
+        assert_eq!(events[2], Event::MatchAborted { reason: AbortReason::NoMoves });
+        assert_eq!(game.phase(), RoomPhase::Lobby);
+    }
+
+    #[test]
+    fn first_moves_are_acknowledged_and_broadcast_as_progress() {
+        let (mut game, p) = started(GameSettings::default(), 3);
+        let events = game.apply(Command::Move { player: p[1], choice: "rock".into() }).unwrap();
+        assert_eq!(events, vec![Event::MoveAccepted { player: p[1], round: 1 }, Event::RoundProgress { round: 1, submitted: vec![p[1]], waiting: vec![p[0], p[2]] }]);
+    }
+
+    #[test]
+    fn countdown_rounds_send_no_progress() {
+        let mut game = Game::with_rules(RuleSet::classic(), countdown());
+        let p = players(3);
+        game.apply(Command::Start { players: p.clone(), format: MatchFormat::Single, mode: ScoringMode::Elimination }).unwrap();
+        game.apply(Command::OpenSubmissions { round: 1 }).unwrap();
+        let events = game.apply(Command::Move { player: p[1], choice: "rock".into() }).unwrap();
+        assert_eq!(events, vec![Event::MoveAccepted { player: p[1], round: 1 }]);
+    }
+
+    #[test]
+    fn countdown_rounds_publish_commitments_when_the_window_closes() {
+        let mut game = Game::with_rules(RuleSet::classic(), GameSettings { commit_reveal: true, ..countdown() });
+        let p = players(2);
+        game.apply(Command::Start { players: p.clone(), format: MatchFormat::Single, mode: ScoringMode::Elimination }).unwrap();
+        game.apply(Command::OpenSubmissions { round: 1 }).unwrap();
+        assert_eq!(commit(&mut game, p[1], "rock", "n1"), vec![Event::MoveAccepted { player: p[1], round: 1 }]);
+        commit(&mut game, p[0], "paper", "n2");
+        let events = game.apply(Command::RoundTimeout { round: 1, entropy: 7 }).unwrap();
+        let hash = |choice, nonce| commit::commitment_hash(choice, nonce);
+        assert_eq!(events[0], Event::MoveCommitted { player: p[0], hash: hash("paper", "n2"), committed: vec![p[0], p[1]] });
+        assert_eq!(events[1], Event::MoveCommitted { player: p[1], hash: hash("rock", "n1"), committed: vec![p[0], p[1]] });
+        assert_eq!(events[2], Event::PhaseChanged { from: RoomPhase::Collecting, to: RoomPhase::Reveal });
+    }
+
+    fn until_lock_in() -> GameSettings {
+        GameSettings { move_changes: MoveChangePolicy::UntilLockIn, round_timeout_secs: Some(10), ..GameSettings::default() }
+    }
+
+    #[test]
+    fn moves_can_be_replaced_until_locked_in() {
+        let (mut game, p) = started(until_lock_in(), 2);
+        assert_eq!(game.apply(Command::LockIn { player: p[0] }), Err(GameError::NoMoveToLock));
+        game.apply(Command::Move { player: p[0], choice: "rock".into() }).unwrap();
+        let events = game.apply(Command::Move { player: p[0], choice: "paper".into() }).unwrap();
+        assert_eq!(events, vec![Event::MoveAccepted { player: p[0], round: 1 }]);
+        assert_eq!(game.move_of(&p[0]), Some("paper"));
+        assert_eq!(game.apply(Command::LockIn { player: p[0] }).unwrap(), vec![Event::MoveLocked { player: p[0], locked: vec![p[0]] }]);
+        assert_eq!(game.apply(Command::Move { player: p[0], choice: "rock".into() }), Err(GameError::MoveLocked));
+        assert_eq!(game.apply(Command::LockIn { player: p[0] }), Err(GameError::MoveLocked));
+    }
+
+    #[test]
+    fn lock_in_is_disabled_when_moves_are_final() {
+        let (mut game, p) = started(GameSettings::default(), 2);
+        game.apply(Command::Move { player: p[0], choice: "rock".into() }).unwrap();
+        assert_eq!(game.apply(Command::LockIn { player: p[0] }), Err(GameError::LockInDisabled));
+    }
+
+    #[test]
+    fn the_round_resolves_once_everyone_has_locked_in() {
+        let (mut game, p) = started(until_lock_in(), 2);
+        game.apply(Command::Move { player: p[0], choice: "rock".into() }).unwrap();
+        game.apply(Command::Move { player: p[1], choice: "scissors".into() }).unwrap();
+        game.apply(Command::LockIn { player: p[0] }).unwrap();
+        assert_eq!(game.phase(), RoomPhase::Collecting);
+        let events = game.apply(Command::LockIn { player: p[1] }).unwrap();
+        assert!(events.contains(&Event::RoundResult { winner: p[0], moves: moves(&[(p[0], "rock"), (p[1], "scissors")]) }));
+    }
+
+    #[test]
+    fn the_deadline_counts_moves_that_were_not_locked_in() {
+        let (mut game, p) = started(until_lock_in(), 3);
+        game.apply(Command::Move { player: p[0], choice: "rock".into() }).unwrap();
+        game.apply(Command::Move { player: p[1], choice: "scissors".into() }).unwrap();
+        game.apply(Command::LockIn { player: p[1] }).unwrap();
+        let events = game.apply(Command::RoundTimeout { round: 1, entropy: 7 }).unwrap();
+        assert_eq!(events[0], Event::PlayerForfeited { player: p[2], reason: ForfeitReason::Timeout });
+        assert!(events.contains(&Event::RoundResult { winner: p[0], moves: moves(&[(p[0], "rock"), (p[1], "scissors")]) }));
+    }
+
+    #[test]
+    fn timeouts_for_an_earlier_round_are_stale() {
+        let (mut game, _) = started(timeout_after(TimeoutPolicy::EndGame), 2);
+        assert_eq!(game.apply(Command::RoundTimeout { round: 0, entropy: 7 }), Err(GameError::StaleTimer));
+        assert_eq!(game.phase(), RoomPhase::Collecting);
+    }
+}
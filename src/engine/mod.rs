@@ -0,0 +1,7 @@
+// Transport-independent game rules; nothing in here may depend on tokio or axum
+pub mod commit;
+pub mod game;
+pub mod phase;
+pub mod rules;
+pub mod scoring;
+pub mod settings;
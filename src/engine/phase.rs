@@ -0,0 +1,56 @@
+use serde::Serialize;
+use std::fmt;
+
+// Lifecycle of a room's game
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RoomPhase {
+    // No game running; players may start one
+    #[default]
+    Lobby,
+    // A round is about to open for submissions
+    Countdown,
+    // Active players are submitting moves
+    Collecting,
+    // All moves are in and the round is being resolved
+    Reveal,
+    // The last game produced a winner; a new one may be started
+    Finished,
+}
+
+impl RoomPhase {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RoomPhase::Lobby => "lobby",
+            RoomPhase::Countdown => "countdown",
+            RoomPhase::Collecting => "collecting",
+            RoomPhase::Reveal => "reveal",
+            RoomPhase::Finished => "finished",
+        }
+    }
+
+    // Whether a game is in progress (between start and its final result)
+    pub fn is_in_game(&self) -> bool {
+        matches!(self, RoomPhase::Countdown | RoomPhase::Collecting | RoomPhase::Reveal)
+    }
+
+    // Legal phase transitions; any phase may fall back to the lobby when a game is aborted, and a
+    // countdown skips straight to the reveal when everyone but one player has left
+    pub fn can_transition_to(&self, next: RoomPhase) -> bool {
+        use RoomPhase::*;
+        matches!(
+            (self, next),
+            (_, Lobby)
+                | (Lobby | Finished, Countdown | Collecting)
+                | (Countdown, Collecting | Reveal)
+                | (Collecting, Reveal)
+                | (Reveal, Countdown | Collecting | Finished)
+        )
+    }
+}
+
+impl fmt::Display for RoomPhase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
@@ -0,0 +1,408 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+// Built-in rule sets selectable when a room is created
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RuleVariant {
+    // rock, paper, scissors
+    #[default]
+    #[serde(alias = "rps")]
+    Classic,
+    // rock, paper, scissors, lizard, spock
+    #[serde(alias = "lizard_spock")]
+    Rpsls,
+    Rps7,
+    Rps15,
+    // Any odd number of caller-supplied moves, each beating the next (n - 1) / 2
+    Balanced,
+    // Caller-supplied moves and "beats" relation, see `RuleDefinition`
+    Custom,
+}
+
+// User-defined rule set, e.g. a themed fire/water/grass game
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RuleDefinition {
+    #[serde(default)]
+    pub name: Option<String>,
+    pub moves: Vec<String>,
+    // move -> moves it beats
+    #[serde(default)]
+    pub beats: HashMap<String, Vec<String>>,
+    // Pairs of distinct moves that tie each other; every pair must be either here or in `beats`
+    #[serde(default)]
+    pub ties: Vec<[String; 2]>,
+}
+
+// Largest rule set accepted; enough for RPS-15 and keeps the n x n "beats" table small
+pub const MAX_MOVES: usize = 25;
+// A custom definition cannot tie more pairs than `MAX_MOVES` moves have
+pub const MAX_TIES: usize = MAX_MOVES * (MAX_MOVES - 1) / 2;
+
+// Moves listed so that each one beats the next (n - 1) / 2, wrapping around
+const CLASSIC: [&str; 3] = ["rock", "scissors", "paper"];
+const RPSLS: [&str; 5] = ["scissors", "lizard", "paper", "spock", "rock"];
+const RPS7: [&str; 7] = ["rock", "fire", "scissors", "sponge", "paper", "air", "water"];
+const RPS15: [&str; 15] = [
+    "rock", "fire", "scissors", "snake", "human", "tree", "wolf", "sponge", "paper", "air", "water", "dragon", "devil", "lightning", "gun",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RuleError {
+    TooFewMoves,
+    TooManyMoves(usize),
+    TooManyTies(usize),
+    EvenMoveCount(usize),
+    EmptyMove,
+    DuplicateMove(String),
+    MissingMoves,
+    MissingDefinition,
+    UnknownMove(String),
+    SelfBeat(String),
+    // The pair is declared both ways (a beats b and b beats a, or beats and tie)
+    Contradiction(String, String),
+    // The pair is neither in `beats` nor in `ties`
+    Unresolved(String, String),
+}
+
+impl fmt::Display for RuleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RuleError::TooFewMoves => write!(f, "A rule set needs at least 3 moves"),
+            RuleError::TooManyMoves(n) => write!(f, "A rule set can have at most {} moves, got {}", MAX_MOVES, n),
+            RuleError::TooManyTies(n) => write!(f, "A rule definition can list at most {} ties, got {}", MAX_TIES, n),
+            RuleError::EvenMoveCount(n) => write!(f, "A balanced rule set needs an odd number of moves, got {}", n),
+            RuleError::EmptyMove => write!(f, "Move names must not be empty"),
+            RuleError::DuplicateMove(name) => write!(f, "Move \"{}\" is listed more than once", name),
+            RuleError::MissingMoves => write!(f, "The balanced variant needs a list of moves"),
+            RuleError::MissingDefinition => write!(f, "The custom variant needs a rule definition"),
+            RuleError::UnknownMove(name) => write!(f, "Move \"{}\" is not in the move list", name),
+            RuleError::SelfBeat(name) => write!(f, "Move \"{}\" cannot beat itself", name),
+            RuleError::Contradiction(a, b) => write!(f, "Moves \"{}\" and \"{}\" have conflicting outcomes", a, b),
+            RuleError::Unresolved(a, b) => write!(f, "Nothing decides \"{}\" against \"{}\"; add it to beats or ties", a, b),
+        }
+    }
+}
+
+impl std::error::Error for RuleError {}
+
+// A set of legal moves and the "beats" relation between them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuleSet {
+    name: String,
+    moves: Vec<String>,
+    // beats[i][j] is true when moves[i] beats moves[j]
+    beats: Vec<Vec<bool>>,
+}
+
+impl Default for RuleSet {
+    fn default() -> Self {
+        RuleSet::classic()
+    }
+}
+
+impl RuleSet {
+    pub fn classic() -> RuleSet {
+        Self::from_builtin("classic", &CLASSIC)
+    }
+
+    // Build a built-in variant; `moves` is only used (and required) for `Balanced`
+    pub fn from_variant(variant: RuleVariant, moves: Option<Vec<String>>) -> Result<RuleSet, RuleError> {
+        match variant {
+            RuleVariant::Classic => Ok(Self::classic()),
+            RuleVariant::Rpsls => Ok(Self::from_builtin("rpsls", &RPSLS)),
+            RuleVariant::Rps7 => Ok(Self::from_builtin("rps7", &RPS7)),
+            RuleVariant::Rps15 => Ok(Self::from_builtin("rps15", &RPS15)),
+            RuleVariant::Balanced => Self::balanced("balanced", moves.ok_or(RuleError::MissingMoves)?),
+            RuleVariant::Custom => Err(RuleError::MissingDefinition),
+        }
+    }
+
+    // Validate a user-defined rule graph: no self-beats, no pair declared twice, every pair decided
+    pub fn from_definition(definition: RuleDefinition) -> Result<RuleSet, RuleError> {
+        let moves = normalize_moves(definition.moves)?;
+        if definition.ties.len() > MAX_TIES {
+            return Err(RuleError::TooManyTies(definition.ties.len()));
+        }
+        let n = moves.len();
+        let index = |name: &str| -> Result<usize, RuleError> {
+            let name = name.trim().to_lowercase();
+            moves.iter().position(|m| *m == name).ok_or(RuleError::UnknownMove(name))
+        };
+        let mut beats = vec![vec![false; n]; n];
+        let mut tied = vec![vec![false; n]; n];
+        for (winner, losers) in &definition.beats {
+            let i = index(winner)?;
+            for loser in losers {
+                let j = index(loser)?;
+                if i == j {
+                    return Err(RuleError::SelfBeat(moves[i].clone()));
+                }
+                if beats[j][i] {
+                    return Err(RuleError::Contradiction(moves[i].clone(), moves[j].clone()));
+                }
+                beats[i][j] = true;
+            }
+        }
+        for [a, b] in &definition.ties {
+            let (i, j) = (index(a)?, index(b)?);
+            if i == j || beats[i][j] || beats[j][i] {
+                return Err(RuleError::Contradiction(moves[i].clone(), moves[j].clone()));
+            }
+            tied[i][j] = true;
+            tied[j][i] = true;
+        }
+        for i in 0..n {
+            for j in (i + 1)..n {
+                if !beats[i][j] && !beats[j][i] && !tied[i][j] {
+                    return Err(RuleError::Unresolved(moves[i].clone(), moves[j].clone()));
+                }
+            }
+        }
+        let name = definition.name.filter(|name| !name.trim().is_empty()).unwrap_or_else(|| "custom".into());
+        Ok(RuleSet { name, moves, beats })
+    }
+
+    // Balanced tournament over an odd number of moves: each move beats the next (n - 1) / 2 in the list
+    pub fn balanced(name: &str, moves: Vec<String>) -> Result<RuleSet, RuleError> {
+        let moves = normalize_moves(moves)?;
+        let n = moves.len();
+        if n % 2 == 0 {
+            return Err(RuleError::EvenMoveCount(n));
+        }
+        let reach = (n - 1) / 2;
+        let beats = (0..n)
+            .map(|i| (0..n).map(|j| j != i && (j + n - i) % n <= reach).collect())
+            .collect();
+        Ok(RuleSet { name: name.to_string(), moves, beats })
+    }
+
+    fn from_builtin(name: &str, moves: &[&str]) -> RuleSet {
+        Self::balanced(name, moves.iter().map(|m| m.to_string()).collect()).expect("built-in rule sets are valid")
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn moves(&self) -> &[String] {
+        &self.moves
+    }
+
+    pub fn index_of(&self, choice: &str) -> Option<usize> {
+        self.moves.iter().position(|m| m == choice)
+    }
+
+    pub fn is_valid_move(&self, choice: &str) -> bool {
+        self.index_of(choice).is_some()
+    }
+
+    pub fn beats(&self, a: &str, b: &str) -> bool {
+        match (self.index_of(a), self.index_of(b)) {
+            (Some(i), Some(j)) => self.beats[i][j],
+            _ => false,
+        }
+    }
+
+    // The present move that beats every other present move, if there is exactly such one
+    pub fn dominant_move<'a>(&self, present: &HashSet<&'a str>) -> Option<&'a str> {
+        if present.len() < 2 {
+            return None;
+        }
+        present
+            .iter()
+            .copied()
+            .find(|candidate| present.iter().all(|other| other == candidate || self.beats(candidate, other)))
+    }
+}
+
+// Lowercase and trim move names, rejecting empty or duplicate entries
+fn normalize_moves(moves: Vec<String>) -> Result<Vec<String>, RuleError> {
+    if moves.len() < 3 {
+        return Err(RuleError::TooFewMoves);
+    }
+    if moves.len() > MAX_MOVES {
+        return Err(RuleError::TooManyMoves(moves.len()));
+    }
+    let mut seen = HashSet::new();
+    let mut normalized = Vec::with_capacity(moves.len());
+    for mv in moves {
+        let mv = mv.trim().to_lowercase();
+        if mv.is_empty() {
+            return Err(RuleError::EmptyMove);
+        }
+        if !seen.insert(mv.clone()) {
+            return Err(RuleError::DuplicateMove(mv));
+        }
+        normalized.push(mv);
+    }
+    Ok(normalized)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(variant: RuleVariant) -> RuleSet {
+        RuleSet::from_variant(variant, None).unwrap()
+    }
+
+    fn strings(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    // Every pair of distinct moves is decided exactly one way
+    fn assert_total(rules: &RuleSet) {
+        for a in rules.moves() {
+            for b in rules.moves() {
+                assert!(!(rules.beats(a, b) && rules.beats(b, a)), "{} and {} beat each other", a, b);
+                assert_eq!(a != b, rules.beats(a, b) || rules.beats(b, a), "{} against {}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn classic_rules_follow_rock_paper_scissors() {
+        let rules = RuleSet::classic();
+        assert!(rules.beats("rock", "scissors"));
+        assert!(rules.beats("scissors", "paper"));
+        assert!(rules.beats("paper", "rock"));
+        assert_total(&rules);
+    }
+
+    #[test]
+    fn rpsls_matches_the_canonical_rules() {
+        let rules = rules(RuleVariant::Rpsls);
+        let wins = [
+            ("scissors", "paper"), ("paper", "rock"), ("rock", "lizard"), ("lizard", "spock"), ("spock", "scissors"),
+            ("scissors", "lizard"), ("lizard", "paper"), ("paper", "spock"), ("spock", "rock"), ("rock", "scissors"),
+        ];
+        for (winner, loser) in wins {
+            assert!(rules.beats(winner, loser), "{} should beat {}", winner, loser);
+            assert!(!rules.beats(loser, winner), "{} should lose to {}", loser, winner);
+        }
+        assert_total(&rules);
+    }
+
+    #[test]
+    fn rps7_matches_the_canonical_rules() {
+        let rules = rules(RuleVariant::Rps7);
+        let wins = [
+            ("rock", "fire"), ("rock", "scissors"), ("rock", "sponge"),
+            ("fire", "scissors"), ("fire", "paper"), ("fire", "sponge"),
+            ("scissors", "air"), ("scissors", "paper"), ("scissors", "sponge"),
+            ("sponge", "paper"), ("sponge", "air"), ("sponge", "water"),
+            ("paper", "air"), ("paper", "rock"), ("paper", "water"),
+            ("air", "fire"), ("air", "rock"), ("air", "water"),
+            ("water", "rock"), ("water", "fire"), ("water", "scissors"),
+        ];
+        for (winner, loser) in wins {
+            assert!(rules.beats(winner, loser), "{} should beat {}", winner, loser);
+        }
+        assert_total(&rules);
+    }
+
+    #[test]
+    fn rps15_moves_beat_the_seven_after_them() {
+        let rules = rules(RuleVariant::Rps15);
+        assert_eq!(rules.moves(), &RPS15[..]);
+        for (i, mv) in RPS15.iter().enumerate() {
+            for step in 1..15 {
+                let other = RPS15[(i + step) % 15];
+                assert_eq!(rules.beats(mv, other), step <= 7, "{} against {}", mv, other);
+            }
+        }
+        assert!(rules.beats("rock", "scissors"));
+        assert!(rules.beats("gun", "rock"));
+        assert!(rules.beats("paper", "gun"));
+    }
+
+    #[test]
+    fn balanced_rules_need_an_odd_number_of_distinct_moves() {
+        assert_eq!(RuleSet::balanced("b", strings(&["a", "b", "c", "d"])), Err(RuleError::EvenMoveCount(4)));
+        assert_eq!(RuleSet::balanced("b", strings(&["a", "b"])), Err(RuleError::TooFewMoves));
+        assert_eq!(RuleSet::balanced("b", strings(&["a", " B", "b"])), Err(RuleError::DuplicateMove("b".into())));
+        assert_eq!(RuleSet::balanced("b", strings(&["a", "", "c"])), Err(RuleError::EmptyMove));
+        let too_many: Vec<String> = (0..=MAX_MOVES).map(|i| format!("move{}", i)).collect();
+        assert_eq!(RuleSet::balanced("b", too_many), Err(RuleError::TooManyMoves(MAX_MOVES + 1)));
+    }
+
+    #[test]
+    fn dominant_move_needs_one_move_beating_all_others() {
+        let rules = rules(RuleVariant::Rpsls);
+        assert_eq!(rules.dominant_move(&["rock", "scissors"].into()), Some("rock"));
+        assert_eq!(rules.dominant_move(&["rock"].into()), None);
+        assert_eq!(rules.dominant_move(&["rock", "paper", "scissors"].into()), None);
+        // With five moves, three distinct ones can still have a move beating both others
+        assert_eq!(rules.dominant_move(&["rock", "scissors", "lizard"].into()), Some("rock"));
+        assert_eq!(rules.dominant_move(&["rock", "paper", "lizard"].into()), None);
+    }
+
+    fn definition(moves: &[&str], beats: &[(&str, &[&str])], ties: &[[&str; 2]]) -> RuleDefinition {
+        RuleDefinition {
+            name: Some("elements".into()),
+            moves: strings(moves),
+            beats: beats.iter().map(|(winner, losers)| (winner.to_string(), strings(losers))).collect(),
+            ties: ties.iter().map(|[a, b]| [a.to_string(), b.to_string()]).collect(),
+        }
+    }
+
+    #[test]
+    fn custom_definition_builds_the_declared_rules() {
+        let rules = RuleSet::from_definition(definition(&["Fire", "water", "grass"], &[("fire", &["grass"]), ("water", &["fire"]), ("grass", &["water"])], &[])).unwrap();
+        assert_eq!(rules.name(), "elements");
+        assert_eq!(rules.moves(), &["fire", "water", "grass"]);
+        assert!(rules.beats("fire", "grass"));
+        assert!(rules.beats("water", "fire"));
+        assert!(rules.beats("grass", "water"));
+        assert_total(&rules);
+    }
+
+    #[test]
+    fn custom_definition_can_tie_pairs() {
+        let rules = RuleSet::from_definition(definition(&["fire", "water", "grass"], &[("water", &["fire"]), ("grass", &["water"])], &[["fire", "grass"]])).unwrap();
+        assert!(!rules.beats("fire", "grass") && !rules.beats("grass", "fire"));
+        assert_eq!(rules.dominant_move(&["fire", "grass"].into()), None);
+    }
+
+    #[test]
+    fn custom_definition_rejects_a_move_beating_itself() {
+        let result = RuleSet::from_definition(definition(&["fire", "water", "grass"], &[("fire", &["fire"])], &[]));
+        assert_eq!(result, Err(RuleError::SelfBeat("fire".into())));
+    }
+
+    #[test]
+    fn custom_definition_rejects_a_pair_beating_each_other() {
+        let result = RuleSet::from_definition(definition(&["fire", "water", "grass"], &[("fire", &["grass"]), ("grass", &["fire"])], &[]));
+        // Which of the two is reported depends on the order `beats` is read in
+        assert!(matches!(result, Err(RuleError::Contradiction(..))), "{:?}", result);
+    }
+
+    #[test]
+    fn custom_definition_rejects_a_pair_both_beaten_and_tied() {
+        let result = RuleSet::from_definition(definition(&["fire", "water", "grass"], &[("fire", &["grass"])], &[["grass", "fire"]]));
+        assert_eq!(result, Err(RuleError::Contradiction("grass".into(), "fire".into())));
+    }
+
+    #[test]
+    fn custom_definition_rejects_an_undecided_pair() {
+        let result = RuleSet::from_definition(definition(&["fire", "water", "grass"], &[("fire", &["grass"]), ("water", &["fire"])], &[]));
+        assert_eq!(result, Err(RuleError::Unresolved("water".into(), "grass".into())));
+    }
+
+    #[test]
+    fn custom_definition_rejects_unknown_moves() {
+        let result = RuleSet::from_definition(definition(&["fire", "water", "grass"], &[("fire", &["ice"])], &[]));
+        assert_eq!(result, Err(RuleError::UnknownMove("ice".into())));
+        let result = RuleSet::from_definition(definition(&["fire", "water", "grass"], &[], &[["fire", "Ice"]]));
+        assert_eq!(result, Err(RuleError::UnknownMove("ice".into())));
+    }
+
+    #[test]
+    fn custom_definition_limits_its_ties() {
+        let ties = vec![["fire", "water"]; MAX_TIES + 1];
+        let result = RuleSet::from_definition(definition(&["fire", "water", "grass"], &[], &ties));
+        assert_eq!(result, Err(RuleError::TooManyTies(MAX_TIES + 1)));
+    }
+}
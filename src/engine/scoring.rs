@@ -0,0 +1,304 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use crate::engine::game::PlayerId;
+use crate::engine::rules::RuleSet;
+
+const MAX_MATCH_LENGTH: u32 = 99;
+
+// How many games make up a match; chosen when the match is started
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MatchFormat {
+    // One game, the first single winner takes the match
+    #[default]
+    Single,
+    // Up to `games` games (odd); a majority of wins takes the match
+    BestOf { games: u32 },
+    // The first player to win `points` games takes the match
+    FirstTo { points: u32 },
+}
+
+impl MatchFormat {
+    pub fn validate(&self) -> Result<(), String> {
+        match *self {
+            MatchFormat::Single => Ok(()),
+            MatchFormat::BestOf { games } if games == 0 || games % 2 == 0 || games > MAX_MATCH_LENGTH => {
+                Err(format!("best_of needs an odd number of games between 1 and {}", MAX_MATCH_LENGTH))
+            }
+            MatchFormat::FirstTo { points } if points == 0 || points > MAX_MATCH_LENGTH => {
+                Err(format!("first_to needs between 1 and {} points", MAX_MATCH_LENGTH))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // Wins that settle the match outright
+    pub fn target(&self) -> u32 {
+        match *self {
+            MatchFormat::Single => 1,
+            MatchFormat::BestOf { games } => games / 2 + 1,
+            MatchFormat::FirstTo { points } => points,
+        }
+    }
+
+    // Games after which the leader takes the match even without reaching the target
+    fn max_games(&self) -> Option<u32> {
+        match *self {
+            MatchFormat::BestOf { games } => Some(games),
+            _ => None,
+        }
+    }
+}
+
+// How a match is played and scored; chosen when the match is started
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ScoringMode {
+    // Losers of each round are knocked out until one player wins the game
+    #[default]
+    Elimination,
+    // Nobody is knocked out: every round each move is scored against every other player's
+    // move and points accumulate over `rounds` rounds
+    RoundRobin { rounds: u32 },
+}
+
+impl ScoringMode {
+    pub fn validate(&self, format: &MatchFormat) -> Result<(), String> {
+        match *self {
+            ScoringMode::Elimination => Ok(()),
+            ScoringMode::RoundRobin { rounds } if rounds == 0 || rounds > MAX_MATCH_LENGTH => {
+                Err(format!("round_robin needs between 1 and {} rounds", MAX_MATCH_LENGTH))
+            }
+            ScoringMode::RoundRobin { .. } if *format != MatchFormat::Single => Err("match formats only apply to elimination mode".into()),
+            ScoringMode::RoundRobin { .. } => Ok(()),
+        }
+    }
+}
+
+// One player's line in a round-robin round: pairwise results against every other move played
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScoreRow {
+    pub player: PlayerId,
+    pub wins: u32,
+    pub ties: u32,
+    pub losses: u32,
+    // Points earned this round (one per pairwise win)
+    pub points: u32,
+    // Points accumulated over the match so far, including this round
+    pub total: u32,
+}
+
+// Score every submitted move against every other one; players without a move score nothing
+pub fn score_pairwise(rules: &RuleSet, players: &[PlayerId], moves: &HashMap<PlayerId, String>) -> Vec<ScoreRow> {
+    players
+        .iter()
+        .map(|player| {
+            let mut row = ScoreRow { player: *player, wins: 0, ties: 0, losses: 0, points: 0, total: 0 };
+            let Some(mine) = moves.get(player) else { return row };
+            for (other, theirs) in moves.iter().filter(|(other, _)| *other != player) {
+                if !players.contains(other) {
+                    continue;
+                }
+                if rules.beats(mine, theirs) {
+                    row.wins += 1;
+                } else if rules.beats(theirs, mine) {
+                    row.losses += 1;
+                } else {
+                    row.ties += 1;
+                }
+            }
+            row.points = row.wins;
+            row
+        })
+        .collect()
+}
+
+// Per-player game wins (elimination) or points (round robin) over a match
+#[derive(Debug, Clone, Default)]
+pub struct Scoreboard {
+    scores: HashMap<PlayerId, u32>,
+    games_played: u32,
+}
+
+impl Scoreboard {
+    pub fn new(players: &[PlayerId]) -> Self {
+        Scoreboard { scores: players.iter().map(|id| (*id, 0)).collect(), games_played: 0 }
+    }
+
+    pub fn scores(&self) -> &HashMap<PlayerId, u32> {
+        &self.scores
+    }
+
+    pub fn games_played(&self) -> u32 {
+        self.games_played
+    }
+
+    pub fn record_win(&mut self, winner: PlayerId) {
+        *self.scores.entry(winner).or_insert(0) += 1;
+        self.games_played += 1;
+    }
+
+    // Add a round-robin round and fill in each row's running total
+    pub fn record_round(&mut self, rows: &mut [ScoreRow]) {
+        for row in rows.iter_mut() {
+            let total = self.scores.entry(row.player).or_insert(0);
+            *total += row.points;
+            row.total = *total;
+        }
+        self.games_played += 1;
+    }
+
+    // Scores of the players still in contention; players who left keep their score but cannot win
+    fn contender_scores<'a>(&'a self, contenders: &'a [PlayerId]) -> impl Iterator<Item = (PlayerId, u32)> + 'a {
+        contenders.iter().map(|id| (*id, self.scores.get(id).copied().unwrap_or(0)))
+    }
+
+    // The single leader among `contenders` once at least `games` games (or rounds) are played; a tie at the top has no leader
+    pub fn leader_after(&self, games: u32, contenders: &[PlayerId]) -> Option<PlayerId> {
+        if self.games_played < games {
+            return None;
+        }
+        let best = self.contender_scores(contenders).map(|(_, score)| score).max()?;
+        let mut leaders = self.contender_scores(contenders).filter(|(_, score)| *score == best);
+        match (leaders.next(), leaders.next()) {
+            (Some((id, _)), None) => Some(id),
+            _ => None,
+        }
+    }
+
+    // The match winner among `contenders` under `format`, if the match is decided. A best-of match whose
+    // games ran out with a tie at the top continues until there is a single leader.
+    pub fn match_winner(&self, format: &MatchFormat, contenders: &[PlayerId]) -> Option<PlayerId> {
+        let (leader, best) = self.contender_scores(contenders).max_by_key(|(_, score)| *score)?;
+        if best >= format.target() {
+            return Some(leader);
+        }
+        format.max_games().and_then(|max| self.leader_after(max, contenders))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    fn players(n: u128) -> Vec<PlayerId> {
+        (1..=n).map(Uuid::from_u128).collect()
+    }
+
+    fn board_after(players: &[PlayerId], winners: &[PlayerId]) -> Scoreboard {
+        let mut board = Scoreboard::new(players);
+        for winner in winners {
+            board.record_win(*winner);
+        }
+        board
+    }
+
+    #[test]
+    fn match_formats_need_a_sensible_length() {
+        assert_eq!(MatchFormat::Single.validate(), Ok(()));
+        assert_eq!(MatchFormat::BestOf { games: 5 }.validate(), Ok(()));
+        assert_eq!(MatchFormat::FirstTo { points: MAX_MATCH_LENGTH }.validate(), Ok(()));
+        for games in [0, 4, MAX_MATCH_LENGTH + 2] {
+            assert!(MatchFormat::BestOf { games }.validate().is_err(), "best_of {}", games);
+        }
+        for points in [0, MAX_MATCH_LENGTH + 1] {
+            assert!(MatchFormat::FirstTo { points }.validate().is_err(), "first_to {}", points);
+        }
+    }
+
+    #[test]
+    fn target_is_the_wins_that_settle_the_match() {
+        assert_eq!(MatchFormat::Single.target(), 1);
+        assert_eq!(MatchFormat::BestOf { games: 1 }.target(), 1);
+        assert_eq!(MatchFormat::BestOf { games: 5 }.target(), 3);
+        assert_eq!(MatchFormat::FirstTo { points: 4 }.target(), 4);
+    }
+
+    #[test]
+    fn a_single_game_match_goes_to_its_first_winner() {
+        let p = players(2);
+        assert_eq!(board_after(&p, &[]).match_winner(&MatchFormat::Single, &p), None);
+        assert_eq!(board_after(&p, &[p[1]]).match_winner(&MatchFormat::Single, &p), Some(p[1]));
+    }
+
+    #[test]
+    fn best_of_is_won_by_a_majority_of_wins() {
+        let p = players(2);
+        let format = MatchFormat::BestOf { games: 5 };
+        assert_eq!(board_after(&p, &[p[0], p[1], p[0]]).match_winner(&format, &p), None);
+        assert_eq!(board_after(&p, &[p[0], p[1], p[0], p[0]]).match_winner(&format, &p), Some(p[0]));
+    }
+
+    #[test]
+    fn best_of_plays_on_while_the_games_run_out_in_a_tie() {
+        let p = players(3);
+        let format = MatchFormat::BestOf { games: 3 };
+        assert_eq!(board_after(&p, &[p[0], p[1], p[2]]).match_winner(&format, &p), None);
+        assert_eq!(board_after(&p, &[p[0], p[1], p[2], p[1]]).match_winner(&format, &p), Some(p[1]));
+        // Once the games are used up the leader wins without reaching the target
+        let p = players(4);
+        assert_eq!(board_after(&p, &[p[0], p[1], p[2], p[3], p[2]]).match_winner(&MatchFormat::BestOf { games: 5 }, &p), Some(p[2]));
+    }
+
+    #[test]
+    fn first_to_has_no_game_limit() {
+        let p = players(3);
+        let format = MatchFormat::FirstTo { points: 2 };
+        assert_eq!(board_after(&p, &[p[0], p[1], p[2]]).match_winner(&format, &p), None);
+        assert_eq!(board_after(&p, &[p[0], p[1], p[2], p[2]]).match_winner(&format, &p), Some(p[2]));
+    }
+
+    #[test]
+    fn pairwise_scoring_counts_each_move_against_every_other() {
+        let p = players(4);
+        let moves: HashMap<PlayerId, String> = [(p[0], "rock"), (p[1], "scissors"), (p[2], "rock")].into_iter().map(|(id, mv)| (id, mv.to_string())).collect();
+        let table = score_pairwise(&RuleSet::classic(), &p, &moves);
+        let row = |wins, ties, losses| (wins, ties, losses, wins);
+        let lines: Vec<_> = table.iter().map(|r| (r.wins, r.ties, r.losses, r.points)).collect();
+        // The player without a move scores nothing and is not scored against
+        assert_eq!(lines, vec![row(1, 1, 0), row(0, 0, 2), row(1, 1, 0), row(0, 0, 0)]);
+        assert_eq!(table.iter().map(|r| r.player).collect::<Vec<_>>(), p);
+    }
+
+    #[test]
+    fn pairwise_scoring_ignores_moves_of_players_outside_the_table() {
+        let p = players(3);
+        let moves: HashMap<PlayerId, String> = [(p[0], "rock"), (p[1], "rock"), (p[2], "paper")].into_iter().map(|(id, mv)| (id, mv.to_string())).collect();
+        let table = score_pairwise(&RuleSet::classic(), &p[..2], &moves);
+        assert!(table.iter().all(|r| r.wins == 0 && r.ties == 1 && r.losses == 0));
+    }
+
+    #[test]
+    fn record_round_keeps_running_totals() {
+        let p = players(2);
+        let mut board = Scoreboard::new(&p);
+        let line = |player, points| ScoreRow { player, wins: points, ties: 0, losses: 0, points, total: 0 };
+        let mut first = vec![line(p[0], 1), line(p[1], 0)];
+        board.record_round(&mut first);
+        let mut second = vec![line(p[0], 0), line(p[1], 3)];
+        board.record_round(&mut second);
+        assert_eq!((first[0].total, first[1].total), (1, 0));
+        assert_eq!((second[0].total, second[1].total), (1, 3));
+        assert_eq!(board.scores()[&p[1]], 3);
+        assert_eq!(board.games_played(), 2);
+    }
+
+    #[test]
+    fn leader_after_waits_for_the_rounds_and_a_single_leader() {
+        let p = players(3);
+        assert_eq!(board_after(&p, &[p[0], p[1]]).leader_after(3, &p), None);
+        assert_eq!(board_after(&p, &[p[0], p[1], p[2]]).leader_after(3, &p), None);
+        assert_eq!(board_after(&p, &[p[0], p[1], p[0]]).leader_after(3, &p), Some(p[0]));
+    }
+
+    #[test]
+    fn players_who_left_cannot_lead_or_win() {
+        let p = players(3);
+        let board = board_after(&p, &[p[0], p[0], p[1]]);
+        assert_eq!(board.leader_after(3, &p[1..]), Some(p[1]));
+        assert_eq!(board.match_winner(&MatchFormat::BestOf { games: 3 }, &p[1..]), Some(p[1]));
+        assert_eq!(board.match_winner(&MatchFormat::FirstTo { points: 2 }, &p[1..]), None);
+        assert_eq!(board.scores()[&p[0]], 2);
+    }
+}
@@ -0,0 +1,103 @@
+use serde::{Deserialize, Serialize};
+use crate::engine::game::MIN_PLAYERS;
+
+const MAX_ROUND_TIMEOUT_SECS: u64 = 300;
+const MAX_COUNTDOWN_SECS: u64 = 10;
+const MAX_SUBMISSION_WINDOW_SECS: u64 = 60;
+const DEFAULT_SUBMISSION_WINDOW_SECS: u64 = 3;
+
+// What happens to active players who have not moved when a round's deadline passes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TimeoutPolicy {
+    // Idle players forfeit; the round is resolved among those who moved
+    #[default]
+    Forfeit,
+    // Idle players are given a random legal move
+    RandomMove,
+    // The match is called off
+    EndGame,
+}
+
+// Whether a submitted move may be replaced before the round closes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MoveChangePolicy {
+    // The first move counts; submitting again is an error
+    #[default]
+    Final,
+    // Moves can be replaced until the player locks in or the round deadline passes;
+    // the round is resolved once every active player has locked in
+    UntilLockIn,
+}
+
+// Per-room game options that outlive a single match
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GameSettings {
+    // Seconds players have to move once a round opens; rounds wait forever when unset.
+    // The engine never reads the clock: the caller fires `Command::RoundTimeout` when it elapses.
+    pub round_timeout_secs: Option<u64>,
+    pub timeout_policy: TimeoutPolicy,
+    // "3-2-1-shoot": every round opens with a countdown of this many seconds, then accepts moves
+    // for `submission_window_secs` and reveals them all when the window closes
+    pub countdown_secs: Option<u64>,
+    pub submission_window_secs: Option<u64>,
+    // Players first commit to a hash of their move, and reveal the move only once everyone has committed
+    pub commit_reveal: bool,
+    pub move_changes: MoveChangePolicy,
+    // Lobby ready-check, run by the server: a match starts by itself once every connected player is ready, and the host
+    // may start it early once `ready_quorum` players are; players who are not ready sit the match out
+    pub ready_check: bool,
+    pub ready_quorum: Option<usize>,
+}
+
+impl GameSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(secs) = self.round_timeout_secs {
+            if secs == 0 || secs > MAX_ROUND_TIMEOUT_SECS {
+                return Err(format!("round_timeout_secs must be between 1 and {}", MAX_ROUND_TIMEOUT_SECS));
+            }
+        }
+        if let Some(secs) = self.countdown_secs {
+            if secs == 0 || secs > MAX_COUNTDOWN_SECS {
+                return Err(format!("countdown_secs must be between 1 and {}", MAX_COUNTDOWN_SECS));
+            }
+        }
+        if let Some(secs) = self.submission_window_secs {
+            if secs == 0 || secs > MAX_SUBMISSION_WINDOW_SECS {
+                return Err(format!("submission_window_secs must be between 1 and {}", MAX_SUBMISSION_WINDOW_SECS));
+            }
+        }
+        if self.commit_reveal && self.move_changes != MoveChangePolicy::Final {
+            return Err("move_changes must be final in commit-reveal rooms".into());
+        }
+        if let Some(quorum) = self.ready_quorum {
+            if !self.ready_check {
+                return Err("ready_quorum requires ready_check".into());
+            }
+            if quorum < MIN_PLAYERS {
+                return Err(format!("ready_quorum must be at least {}", MIN_PLAYERS));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn allows_move_changes(&self) -> bool {
+        self.move_changes == MoveChangePolicy::UntilLockIn
+    }
+
+    // Whether rounds are resolved only when their deadline passes, even if every move is in
+    pub fn reveal_at_deadline(&self) -> bool {
+        self.countdown_secs.is_some()
+    }
+
+    // Seconds from a round opening for moves (and, in commit-reveal rooms, for reveals) until `Command::RoundTimeout`
+    pub fn round_deadline_secs(&self) -> Option<u64> {
+        if self.countdown_secs.is_some() {
+            Some(self.submission_window_secs.unwrap_or(DEFAULT_SUBMISSION_WINDOW_SECS))
+        } else {
+            self.round_timeout_secs
+        }
+    }
+}
@@ -1 +1,2 @@
+pub mod engine;
 pub mod server;
\ No newline at end of file
@@ -0,0 +1,201 @@
+use serde::Deserialize;
+use serde_json::Value;
+use crate::engine::scoring::{MatchFormat, ScoringMode};
+use crate::engine::settings::GameSettings;
+
+const MAX_NONCE_LENGTH: usize = 128;
+const MAX_NAME_LENGTH: usize = 24;
+const MAX_AVATAR_LENGTH: usize = 32;
+const MAX_BAN_SECS: u64 = 24 * 60 * 60;
+
+// Commands a client can send on a `/join/{room_id}` socket, tagged by the `action` field
+#[derive(Debug, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum ClientAction {
+    // Set the sender's display name and optional avatar id
+    Hello { name: String, avatar: Option<String> },
+    // Host only: start a new match with every connected client, in the room's match format unless `format` or `mode` is given
+    #[serde(alias = "start_game")]
+    Start { format: Option<MatchFormat>, mode: Option<ScoringMode> },
+    // Submit a move for the current round
+    Move { choice: String },
+    // Make the submitted move final (rooms where moves can change until lock-in)
+    LockIn,
+    // Commit-reveal rooms: commit to a move with the hex SHA-256 of "{choice}:{nonce}"
+    Commit { hash: String },
+    // Commit-reveal rooms: reveal the committed move once everyone has committed
+    Reveal { choice: String, nonce: String },
+    // Ready-check rooms: mark the sender ready (or no longer ready) for the next match
+    Ready,
+    Unready,
+    // Send a chat message to everyone in the room
+    Chat { message: String },
+    // Host only, between matches: replace the room's settings (omitted fields take their defaults)
+    UpdateSettings { settings: GameSettings },
+    // Host only, between matches: change how many players the room seats and how many a match needs
+    UpdateLimits { capacity: Option<usize>, min_players: Option<usize> },
+    // Host only: refuse (or accept again) new players
+    Lock,
+    Unlock,
+    // Host only: refuse (or allow again) chat messages from the player with this id
+    Mute { player: String },
+    Unmute { player: String },
+    // Host only: remove the player with this id from the room, optionally banning them for `ban_secs` seconds
+    Kick { player: String, ban_secs: Option<u64> },
+    // Leave the room and close the connection
+    Leave,
+}
+
+impl ClientAction {
+    // Parse and validate a text frame; the error string is sent back to the client as-is
+    pub fn parse(text: &str) -> Result<ClientAction, String> {
+        let mut value: Value = serde_json::from_str(text).map_err(|e| format!("Invalid JSON: {}", e))?;
+        let obj = value.as_object_mut().ok_or_else(|| "Expected a JSON object with an \"action\" field".to_string())?;
+        // Action names are matched case-insensitively
+        let name = match obj.get_mut("action") {
+            Some(Value::String(action)) => {
+                *action = action.to_lowercase();
+                action.clone()
+            }
+            Some(_) => return Err("Field \"action\" must be a string".into()),
+            None => return Err("Missing field \"action\"".into()),
+        };
+        let action: ClientAction = serde_json::from_value(value).map_err(|e| {
+            if name.is_empty() { format!("Invalid action: {}", e) } else { format!("Invalid \"{}\" action: {}", name, e) }
+        })?;
+        action.validate()
+    }
+
+    // Per-variant validation and normalization of payload fields
+    fn validate(self) -> Result<ClientAction, String> {
+        match self {
+            ClientAction::Move { choice } => {
+                // Whether the choice is legal is decided by the game rules
+                let choice = choice.trim().to_lowercase();
+                if choice.is_empty() {
+                    return Err("Move choice must not be empty".into());
+                }
+                Ok(ClientAction::Move { choice })
+            }
+            ClientAction::Reveal { choice, nonce } => {
+                // The nonce is hashed exactly as sent; only the choice is normalized
+                let choice = choice.trim().to_lowercase();
+                if choice.is_empty() {
+                    return Err("Reveal choice must not be empty".into());
+                }
+                if nonce.is_empty() || nonce.len() > MAX_NONCE_LENGTH {
+                    return Err(format!("Reveal nonce must be 1-{} bytes", MAX_NONCE_LENGTH));
+                }
+                Ok(ClientAction::Reveal { choice, nonce })
+            }
+            ClientAction::Hello { name, avatar } => {
+                let name = validate_name(&name)?;
+                let avatar = avatar.as_deref().map(validate_avatar).transpose()?;
+                Ok(ClientAction::Hello { name, avatar })
+            }
+            ClientAction::Chat { message } => {
+                // The length limit is server configuration, checked when the message is posted
+                let message = message.trim().to_string();
+                if message.is_empty() {
+                    return Err("Chat message must not be empty".into());
+                }
+                Ok(ClientAction::Chat { message })
+            }
+            ClientAction::Kick { player, ban_secs } => {
+                if ban_secs.is_some_and(|secs| secs == 0 || secs > MAX_BAN_SECS) {
+                    return Err(format!("Kick ban_secs must be between 1 and {}", MAX_BAN_SECS));
+                }
+                Ok(ClientAction::Kick { player, ban_secs })
+            }
+            action => Ok(action),
+        }
+    }
+}
+
+// Display names: 1-24 letters, digits, spaces and `_-.`, without surrounding whitespace
+pub fn validate_name(name: &str) -> Result<String, String> {
+    let name = name.trim();
+    if name.is_empty() || name.chars().count() > MAX_NAME_LENGTH {
+        return Err(format!("Name must be 1-{} characters", MAX_NAME_LENGTH));
+    }
+    if !name.chars().all(|c| c.is_alphanumeric() || matches!(c, ' ' | '_' | '-' | '.')) {
+        return Err("Name may only contain letters, digits, spaces and _-.".into());
+    }
+    Ok(name.to_string())
+}
+
+// Avatar ids: 1-32 ASCII letters, digits, `_` and `-`, chosen from the client's own catalogue
+pub fn validate_avatar(avatar: &str) -> Result<String, String> {
+    if avatar.is_empty() || avatar.len() > MAX_AVATAR_LENGTH || !avatar.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-')) {
+        return Err(format!("Avatar must be 1-{} characters of a-z, 0-9, _ and -", MAX_AVATAR_LENGTH));
+    }
+    Ok(avatar.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn action_names_are_matched_case_insensitively() {
+        assert!(matches!(ClientAction::parse(r#"{"action":"LOCK_IN"}"#), Ok(ClientAction::LockIn)));
+        assert!(matches!(ClientAction::parse(r#"{"action":"Move","choice":"rock"}"#), Ok(ClientAction::Move { .. })));
+    }
+
+    #[test]
+    fn start_game_is_an_alias_of_start() {
+        let action = ClientAction::parse(r#"{"action":"start_game","format":{"type":"best_of","games":3}}"#);
+        assert!(matches!(action, Ok(ClientAction::Start { format: Some(MatchFormat::BestOf { games: 3 }), mode: None })), "{:?}", action);
+    }
+
+    #[test]
+    fn action_must_be_a_string_field_of_an_object() {
+        assert!(ClientAction::parse("move").unwrap_err().starts_with("Invalid JSON"));
+        assert_eq!(ClientAction::parse(r#"["move"]"#).unwrap_err(), "Expected a JSON object with an \"action\" field");
+        assert_eq!(ClientAction::parse(r#"{"choice":"rock"}"#).unwrap_err(), "Missing field \"action\"");
+        assert_eq!(ClientAction::parse(r#"{"action":7}"#).unwrap_err(), "Field \"action\" must be a string");
+        assert!(ClientAction::parse(r#"{"action":"dance"}"#).unwrap_err().starts_with("Invalid \"dance\" action"));
+        assert!(ClientAction::parse(r#"{"action":"move"}"#).unwrap_err().starts_with("Invalid \"move\" action"));
+    }
+
+    #[test]
+    fn moves_and_reveals_are_normalized_and_need_a_choice() {
+        assert!(matches!(ClientAction::parse(r#"{"action":"move","choice":" Rock "}"#), Ok(ClientAction::Move { choice }) if choice == "rock"));
+        assert_eq!(ClientAction::parse(r#"{"action":"move","choice":"  "}"#).unwrap_err(), "Move choice must not be empty");
+        let reveal = ClientAction::parse(r#"{"action":"reveal","choice":"Paper","nonce":" N1"}"#);
+        assert!(matches!(reveal, Ok(ClientAction::Reveal { choice, nonce }) if choice == "paper" && nonce == " N1"));
+        assert_eq!(ClientAction::parse(r#"{"action":"reveal","choice":"","nonce":"n1"}"#).unwrap_err(), "Reveal choice must not be empty");
+    }
+
+    #[test]
+    fn reveal_nonces_are_bounded() {
+        let expected = format!("Reveal nonce must be 1-{} bytes", MAX_NONCE_LENGTH);
+        assert_eq!(ClientAction::parse(r#"{"action":"reveal","choice":"rock","nonce":""}"#).unwrap_err(), expected);
+        let long = format!(r#"{{"action":"reveal","choice":"rock","nonce":"{}"}}"#, "n".repeat(MAX_NONCE_LENGTH + 1));
+        assert_eq!(ClientAction::parse(&long).unwrap_err(), expected);
+    }
+
+    #[test]
+    fn hello_validates_the_profile() {
+        let hello = ClientAction::parse(r#"{"action":"hello","name":" Ann-Marie ","avatar":"cat_2"}"#);
+        assert!(matches!(hello, Ok(ClientAction::Hello { name, avatar }) if name == "Ann-Marie" && avatar.as_deref() == Some("cat_2")));
+        assert_eq!(ClientAction::parse(r#"{"action":"hello","name":""}"#).unwrap_err(), format!("Name must be 1-{} characters", MAX_NAME_LENGTH));
+        assert_eq!(ClientAction::parse(r#"{"action":"hello","name":"<b>"}"#).unwrap_err(), "Name may only contain letters, digits, spaces and _-.");
+        assert!(ClientAction::parse(r#"{"action":"hello","name":"Ann","avatar":"c a t"}"#).unwrap_err().starts_with("Avatar must be"));
+    }
+
+    #[test]
+    fn chat_messages_are_trimmed_and_must_not_be_empty() {
+        assert!(matches!(ClientAction::parse(r#"{"action":"chat","message":" hi "}"#), Ok(ClientAction::Chat { message }) if message == "hi"));
+        assert_eq!(ClientAction::parse(r#"{"action":"chat","message":" "}"#).unwrap_err(), "Chat message must not be empty");
+    }
+
+    #[test]
+    fn kick_bans_are_bounded() {
+        let expected = format!("Kick ban_secs must be between 1 and {}", MAX_BAN_SECS);
+        assert!(matches!(ClientAction::parse(r#"{"action":"kick","player":"p"}"#), Ok(ClientAction::Kick { ban_secs: None, .. })));
+        assert_eq!(ClientAction::parse(r#"{"action":"kick","player":"p","ban_secs":0}"#).unwrap_err(), expected);
+        let long = format!(r#"{{"action":"kick","player":"p","ban_secs":{}}}"#, MAX_BAN_SECS + 1);
+        assert_eq!(ClientAction::parse(&long).unwrap_err(), expected);
+    }
+}
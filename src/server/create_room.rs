@@ -0,0 +1,197 @@
+use axum::extract::rejection::JsonRejection;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde::Deserialize;
+use std::time::Duration;
+use uuid::Uuid;
+use crate::engine::game::{Game, MIN_PLAYERS};
+use crate::engine::rules::{RuleDefinition, RuleError, RuleSet, RuleVariant};
+use crate::engine::scoring::{MatchFormat, ScoringMode};
+use crate::engine::settings::GameSettings;
+use crate::server::responses::{CreateRoomResponse, ErrorResponse};
+use crate::server::password::RoomPassword;
+use crate::server::server::{Room, ServerConfig, SharedState};
+
+const MAX_ROOM_ID_LENGTH: usize = 64;
+const GENERATED_ROOM_ID_LENGTH: usize = 8;
+// Private rooms are only reachable by id, so theirs are harder to guess
+const GENERATED_PRIVATE_ROOM_ID_LENGTH: usize = 32;
+
+// Body of `POST /rooms`; every field is optional
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct CreateRoomRequest {
+    // Id to create the room under; the server picks one when omitted
+    pub room_id: Option<String>,
+    // Most players the room seats, and fewest a match can start with
+    pub capacity: Option<usize>,
+    pub min_players: Option<usize>,
+    pub rules: RuleVariant,
+    // Move list for the `balanced` variant
+    pub moves: Option<Vec<String>>,
+    // Rule definition for the `custom` variant
+    pub definition: Option<RuleDefinition>,
+    pub settings: GameSettings,
+    // Match format and scoring mode used when `start` does not name one
+    pub format: MatchFormat,
+    pub mode: ScoringMode,
+    // Leave the room out of room lists
+    pub private: bool,
+    // Require this password from players joining the room
+    pub password: Option<String>,
+}
+
+impl CreateRoomRequest {
+    // Build the (empty) room, or an error code and message
+    fn room(self, config: &ServerConfig) -> Result<Room, (&'static str, String)> {
+        let capacity = self.capacity.unwrap_or(config.max_room_capacity);
+        let min_players = self.min_players.unwrap_or(MIN_PLAYERS);
+        config.check_room_limits(capacity, min_players).map_err(|err| ("invalid_limits", err))?;
+        let rules = match (self.rules, self.definition) {
+            (RuleVariant::Custom, Some(definition)) => RuleSet::from_definition(definition),
+            (RuleVariant::Custom, None) => Err(RuleError::MissingDefinition),
+            (variant, _) => RuleSet::from_variant(variant, self.moves),
+        };
+        let rules = rules.map_err(|err| ("invalid_rules", format!("Invalid rules: {}", err)))?;
+        self.settings.validate().map_err(|err| ("invalid_settings", format!("Invalid settings: {}", err)))?;
+        self.format.validate().map_err(|err| ("invalid_format", format!("Invalid format: {}", err)))?;
+        self.mode.validate(&self.format).map_err(|err| ("invalid_format", format!("Invalid format: {}", err)))?;
+
+        let password = self.password.as_deref().map(RoomPassword::new).transpose().map_err(|err| ("invalid_password", format!("Invalid password: {}", err)))?;
+
+        let mut room = Room::new(Game::with_rules(rules, self.settings), capacity, min_players);
+        room.format = self.format;
+        room.mode = self.mode;
+        room.private = self.private;
+        room.password = password;
+        Ok(room)
+    }
+}
+
+// Room ids picked by clients: 1-64 ASCII letters, digits, `_` and `-`, so they fit in a URL path as-is
+fn validate_room_id(room_id: &str) -> Result<(), String> {
+    if room_id.is_empty() || room_id.len() > MAX_ROOM_ID_LENGTH || !room_id.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-')) {
+        return Err(format!("room_id must be 1-{} characters of a-z, 0-9, _ and -", MAX_ROOM_ID_LENGTH));
+    }
+    Ok(())
+}
+
+// `POST /rooms`: create a room up front, with its rules and settings, and answer with where to join it
+pub async fn create_room(State(state): State<SharedState>, body: Result<Json<CreateRoomRequest>, JsonRejection>) -> Response {
+    let Json(request) = match body {
+        Ok(body) => body,
+        Err(rejection) => return ErrorResponse::http(StatusCode::BAD_REQUEST, None, "invalid_request", rejection.body_text()),
+    };
+    let requested_id = request.room_id.clone();
+    let id_length = if request.private { GENERATED_PRIVATE_ROOM_ID_LENGTH } else { GENERATED_ROOM_ID_LENGTH };
+    if let Some(Err(err)) = requested_id.as_deref().map(validate_room_id) {
+        return ErrorResponse::http(StatusCode::BAD_REQUEST, requested_id, "invalid_room_id", err);
+    }
+    // Rules are parsed without holding the state lock, so a costly definition does not hold up other rooms
+    let config = state.lock().await.config.clone();
+    let room = match request.room(&config) {
+        Ok(room) => room,
+        Err((code, message)) => return ErrorResponse::http(StatusCode::BAD_REQUEST, requested_id, code, message),
+    };
+
+    let mut app = state.lock().await;
+    let room_id = match requested_id {
+        Some(room_id) if app.rooms.contains_key(&room_id) => {
+            return ErrorResponse::http(StatusCode::CONFLICT, Some(room_id), "room_exists", "A room with this id already exists");
+        }
+        Some(room_id) => room_id,
+        None => loop {
+            let room_id: String = Uuid::new_v4().simple().to_string().chars().take(id_length).collect();
+            if !app.rooms.contains_key(&room_id) {
+                break room_id;
+            }
+        },
+    };
+    let nonce = room.nonce;
+    app.rooms.insert(room_id.clone(), room);
+    app.broadcast_room_list();
+    println!("Room {} created", room_id);
+
+    // A room nobody joins is dropped after a while; once joined, it goes away when its last player leaves
+    let ttl = Duration::from_secs(config.empty_room_ttl_secs);
+    let (state, expiring_id) = (state.clone(), room_id.clone());
+    tokio::spawn(async move {
+        tokio::time::sleep(ttl).await;
+        let mut app = state.lock().await;
+        // A room recreated under the same id since then has its own timer
+        if app.rooms.get(&expiring_id).is_some_and(|room| room.nonce == nonce && room.clients.is_empty()) {
+            app.rooms.remove(&expiring_id);
+            app.broadcast_room_list();
+        }
+    });
+
+    let join_url = config.join_url(&room_id);
+    (StatusCode::CREATED, Json(CreateRoomResponse { room_id, join_url })).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error_code(request: CreateRoomRequest) -> &'static str {
+        match request.room(&ServerConfig::default()) {
+            Ok(_) => panic!("expected the request to be refused"),
+            Err((code, _)) => code,
+        }
+    }
+
+    #[test]
+    fn room_ids_fit_in_a_url_path() {
+        assert!(validate_room_id("Finals_2-b").is_ok());
+        assert!(validate_room_id(&"a".repeat(MAX_ROOM_ID_LENGTH)).is_ok());
+        assert!(validate_room_id("").is_err());
+        assert!(validate_room_id(&"a".repeat(MAX_ROOM_ID_LENGTH + 1)).is_err());
+        for id in ["a b", "a/b", "a?b", "ä", "a.b"] {
+            assert!(validate_room_id(id).is_err(), "{}", id);
+        }
+    }
+
+    #[test]
+    fn an_empty_request_builds_a_default_room() {
+        let room = CreateRoomRequest::default().room(&ServerConfig::default()).unwrap();
+        assert_eq!((room.capacity, room.min_players), (ServerConfig::default().max_room_capacity, MIN_PLAYERS));
+        assert_eq!(room.game.rules().name(), RuleSet::classic().name());
+        assert!(room.clients.is_empty() && room.host.is_none());
+        assert!(!room.private && room.password.is_none());
+    }
+
+    #[test]
+    fn the_request_picks_the_room_rules_settings_and_access() {
+        let request = CreateRoomRequest {
+            capacity: Some(4),
+            min_players: Some(3),
+            rules: RuleVariant::Rpsls,
+            settings: GameSettings { round_timeout_secs: Some(30), ..GameSettings::default() },
+            format: MatchFormat::BestOf { games: 3 },
+            private: true,
+            password: Some("hunter2".into()),
+            ..CreateRoomRequest::default()
+        };
+        let room = request.room(&ServerConfig::default()).unwrap();
+        assert_eq!((room.capacity, room.min_players), (4, 3));
+        assert_eq!(room.game.rules().moves().len(), 5);
+        assert_eq!(room.game.settings().round_timeout_secs, Some(30));
+        assert_eq!(room.format, MatchFormat::BestOf { games: 3 });
+        assert!(room.private);
+        assert!(room.password.is_some_and(|password| password.verify("hunter2")));
+    }
+
+    #[test]
+    fn each_invalid_field_has_its_own_error_code() {
+        assert_eq!(error_code(CreateRoomRequest { capacity: Some(1), ..CreateRoomRequest::default() }), "invalid_limits");
+        assert_eq!(error_code(CreateRoomRequest { rules: RuleVariant::Custom, ..CreateRoomRequest::default() }), "invalid_rules");
+        let settings = GameSettings { round_timeout_secs: Some(0), ..GameSettings::default() };
+        assert_eq!(error_code(CreateRoomRequest { settings, ..CreateRoomRequest::default() }), "invalid_settings");
+        assert_eq!(error_code(CreateRoomRequest { format: MatchFormat::BestOf { games: 2 }, ..CreateRoomRequest::default() }), "invalid_format");
+        let mode = ScoringMode::RoundRobin { rounds: 3 };
+        assert_eq!(error_code(CreateRoomRequest { format: MatchFormat::BestOf { games: 3 }, mode, ..CreateRoomRequest::default() }), "invalid_format");
+        assert_eq!(error_code(CreateRoomRequest { password: Some(String::new()), ..CreateRoomRequest::default() }), "invalid_password");
+    }
+}
@@ -0,0 +1,112 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use uuid::Uuid;
+use crate::engine::game::{Command, Event, GameError};
+use crate::engine::phase::RoomPhase;
+use crate::server::responses::{CountdownTickResponse, RoundTimerResponse, ServerEvent};
+use crate::server::server::{Room, SharedState};
+
+// Feed a command to the room's game, broadcast the resulting events and arm round deadlines.
+// Rejected commands leave the room untouched and are returned to the caller to report.
+pub fn apply_command(state: &SharedState, room_id: &str, room: &mut Room, command: Command) -> Result<(), GameError> {
+    let events = room.game.apply(command)?;
+    publish(state, room_id, room, events);
+    Ok(())
+}
+
+// Broadcast engine events to the room; `CountdownStarted`, `RoundOpened` and `RevealOpened` are internal and drive the round timers
+pub fn publish(state: &SharedState, room_id: &str, room: &mut Room, events: Vec<Event>) {
+    for event in events {
+        match event {
+            Event::CountdownStarted { round, secs } => start_countdown(state, room_id, room, round, secs),
+            Event::RoundOpened { round } | Event::RevealOpened { round } => arm_round_timer(state, room_id, room, round),
+            event => {
+                // Acknowledgements go to the submitter only
+                let recipient = match &event {
+                    Event::MoveAccepted { player, .. } => Some(*player),
+                    _ => None,
+                };
+                if let Some(event) = ServerEvent::from_game_event(room_id, event, &|id| room.player_ref(id)) {
+                    match recipient {
+                        Some(player) => room.send_to(&player, &event),
+                        None => room.broadcast(&event),
+                    }
+                }
+            }
+        }
+    }
+    // A timer left over from a finished or aborted game has nothing to expire
+    if !matches!(room.game.phase(), RoomPhase::Countdown | RoomPhase::Collecting | RoomPhase::Reveal) {
+        cancel_round_timer(room);
+    }
+}
+
+pub fn cancel_round_timer(room: &mut Room) {
+    if let Some(timer) = room.round_timer.take() {
+        timer.abort();
+    }
+}
+
+// Tick once a second down to zero ("shoot"), then open the round for moves
+fn start_countdown(state: &SharedState, room_id: &str, room: &mut Room, round: u32, secs: u64) {
+    cancel_round_timer(room);
+    let started = tokio::time::Instant::now();
+    let shoot_at_ms = unix_millis(SystemTime::now() + Duration::from_secs(secs));
+
+    let state = state.clone();
+    let room_id = room_id.to_string();
+    let task = tokio::spawn(async move {
+        for remaining in (0..=secs).rev() {
+            tokio::time::sleep_until(started + Duration::from_secs(secs - remaining)).await;
+            let mut app = state.lock().await;
+            let Some(room) = app.rooms.get_mut(&room_id) else { return };
+            if room.game.round() != round {
+                return;
+            }
+            room.broadcast(&ServerEvent::CountdownTick(CountdownTickResponse {
+                room_id: room_id.clone(),
+                round,
+                remaining,
+                server_time_ms: unix_millis(SystemTime::now()),
+                shoot_at_ms,
+            }));
+            if remaining == 0 {
+                // Opening the round arms its deadline in place of this task
+                room.round_timer = None;
+                let _ = apply_command(&state, &room_id, room, Command::OpenSubmissions { round });
+            }
+        }
+    });
+    room.round_timer = Some(task.abort_handle());
+}
+
+// Start the deadline for `round` if the room has one, replacing any previous timer
+fn arm_round_timer(state: &SharedState, room_id: &str, room: &mut Room, round: u32) {
+    cancel_round_timer(room);
+    let Some(secs) = room.game.settings().round_deadline_secs() else { return };
+    let timeout = Duration::from_secs(secs);
+    room.broadcast(&ServerEvent::RoundTimer(RoundTimerResponse {
+        room_id: room_id.to_string(),
+        round,
+        deadline_ms: unix_millis(SystemTime::now() + timeout),
+        timeout_secs: secs,
+        policy: room.game.settings().timeout_policy,
+    }));
+
+    let state = state.clone();
+    let room_id = room_id.to_string();
+    let task = tokio::spawn(async move {
+        tokio::time::sleep(timeout).await;
+        let mut app = state.lock().await;
+        let Some(room) = app.rooms.get_mut(&room_id) else { return };
+        // This timer is done; don't let the next round's arming abort the running task
+        room.round_timer = None;
+        let entropy = Uuid::new_v4().as_u128() as u64;
+        // A stale deadline (round already resolved) is simply ignored
+        let _ = apply_command(&state, &room_id, room, Command::RoundTimeout { round, entropy });
+    });
+    room.round_timer = Some(task.abort_handle());
+}
+
+pub fn unix_millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0)
+}
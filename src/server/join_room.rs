@@ -1,289 +1,681 @@
 use axum::extract::ws::{Message, WebSocket};
-use axum::extract::{State, WebSocketUpgrade, Path};
-use axum::response::IntoResponse;
+use axum::extract::{ConnectInfo, State, WebSocketUpgrade, Path, Query};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use futures::stream::SplitSink;
 use futures::{SinkExt, StreamExt};
-use std::collections::{HashMap, HashSet};
-use tokio::sync::mpsc;
+use serde::Deserialize;
+use std::collections::hash_map::Entry;
+use std::collections::VecDeque;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+use tokio::sync::{mpsc, Notify};
 use uuid::Uuid;
-use crate::server::responses::{JoinRoomResponse, RoomInfo, RoomListResponse, GameStartedResponse, RoundResultResponse, ErrorResponse, RematchResponse};
+use crate::server::actions::{validate_avatar, validate_name, ClientAction};
+use crate::engine::game::{Command, Game, GameError, MIN_PLAYERS};
+use crate::engine::rules::{RuleDefinition, RuleError, RuleSet, RuleVariant};
+use crate::engine::scoring::{MatchFormat, ScoringMode};
+use crate::engine::settings::{GameSettings, MoveChangePolicy, TimeoutPolicy};
+use crate::server::{game_loop, moderation};
+use crate::server::password::RoomPassword;
+use crate::server::responses::{JoinRoomResponse, ErrorResponse, ChatHistoryResponse, HostChangedResponse, KickedResponse, LimitsUpdatedResponse, PlayerDisconnectedResponse, PlayerMutedResponse, PlayerReadyResponse, PlayerReconnectedResponse, ProfileUpdatedResponse, RoomLockedResponse, ServerEvent};
+use crate::server::server::{AppState, Ban, ClientEntry, Room, ServerConfig, SharedState, Tx};
 
-const MAX_PLAYERS_PER_ROOM: usize = 10;
+// How long a dropped player's seat is held for them to reconnect with their resume token
+const RESUME_GRACE_SECS: u64 = 30;
 
-// Outcome type for a completed round among active players
-enum Outcome {
-    Tie { moves: HashMap<String, String> },
-    MultiWinners { winners: Vec<Uuid>, moves: HashMap<String, String> },
-    SingleWinner { winner: Uuid, moves: HashMap<String, String> },
+// Per-connection context handed to the action dispatcher
+struct Session {
+    state: SharedState,
+    room_id: String,
+    client_id: Uuid,
+    tx: Tx,
 }
 
-// Compute outcome for current active players
-fn compute_round_outcome(active_players: &HashSet<Uuid>, moves: &HashMap<Uuid, String>) -> Outcome {
-    // Consider only active players' moves
-    let mut unique: std::collections::HashSet<&str> = std::collections::HashSet::new();
-    for pid in active_players {
-        if let Some(mv) = moves.get(pid) { unique.insert(mv.as_str()); }
-    }
-    // Prepare moves map for payload
-    let mut moves_map: HashMap<String, String> = HashMap::new();
-    for pid in active_players {
-        if let Some(mv) = moves.get(pid) { moves_map.insert(pid.to_string(), mv.clone()); }
+impl Session {
+    // Send an ErrorResponse to this client only
+    fn send_error(&self, code: &str, message: impl Into<String>) {
+        let err = ErrorResponse { room_id: Some(self.room_id.clone()), code: code.into(), message: message.into(), my_id: Some(self.client_id.to_string()) };
+        let _ = self.tx.send(ServerEvent::Error(err).to_json());
     }
+}
 
-    if unique.len() == 1 || unique.len() == 3 {
-        return Outcome::Tie { moves: moves_map };
+// Single entry point for every parsed client action; returns false when the connection should be closed
+async fn dispatch(session: &Session, action: ClientAction) -> bool {
+    match action {
+        ClientAction::Hello { name, avatar } => set_profile(session, name, avatar).await,
+        ClientAction::Start { format, mode } => start_game(session, format, mode).await,
+        ClientAction::Move { choice } => submit_move(session, choice).await,
+        ClientAction::LockIn => lock_in(session).await,
+        ClientAction::Commit { hash } => commit_move(session, hash).await,
+        ClientAction::Reveal { choice, nonce } => reveal_move(session, choice, nonce).await,
+        ClientAction::UpdateSettings { settings } => update_settings(session, settings).await,
+        ClientAction::UpdateLimits { capacity, min_players } => update_limits(session, capacity, min_players).await,
+        ClientAction::Lock => set_locked(session, true).await,
+        ClientAction::Unlock => set_locked(session, false).await,
+        ClientAction::Ready => set_ready(session, true).await,
+        ClientAction::Unready => set_ready(session, false).await,
+        ClientAction::Chat { message } => relay_chat(session, message).await,
+        ClientAction::Mute { player } => set_muted(session, &player, true).await,
+        ClientAction::Unmute { player } => set_muted(session, &player, false).await,
+        ClientAction::Kick { player, ban_secs } => kick_player(session, &player, ban_secs).await,
+        ClientAction::Leave => return false,
     }
+    true
+}
+
+async fn set_profile(session: &Session, name: String, avatar: Option<String>) {
+    let mut app = session.state.lock().await;
+    let Some(room) = app.rooms.get_mut(&session.room_id) else { return };
+    let Some(client) = room.clients.get_mut(&session.client_id) else { return };
+    client.name = Some(name);
+    client.avatar = avatar;
+    room.broadcast(&ServerEvent::ProfileUpdated(ProfileUpdatedResponse { room_id: session.room_id.clone(), player: room.player_ref(&session.client_id) }));
+}
 
-    // unique.len() == 2: find winning move
-    let has_rock = unique.contains("rock");
-    let has_paper = unique.contains("paper");
-    let has_scissors = unique.contains("scissors");
-    let winning_move = if has_rock && has_scissors {
-        Some("rock")
-    } else if has_paper && has_rock {
-        Some("paper")
-    } else if has_scissors && has_paper {
-        Some("scissors")
+async fn start_game(session: &Session, format: Option<MatchFormat>, mode: Option<ScoringMode>) {
+    let mut app = session.state.lock().await;
+    let Some(room) = app.rooms.get_mut(&session.room_id) else { return };
+    if !is_host(session, room) {
+        return;
+    }
+    // Readiness is used up by the running match, so report that rather than a ready count
+    if room.game.is_active() {
+        let err = GameError::GameInProgress(room.game.phase());
+        session.send_error(err.code(), err.to_string());
+        return;
+    }
+    // Players away on a dropped connection sit this match out, and so do players who are not ready in ready-check rooms
+    let players = if room.game.settings().ready_check {
+        match room.ready_to_start(room.game.settings().ready_quorum) {
+            Ok(ready) => ready,
+            Err((ready, needed)) => {
+                session.send_error("not_ready", format!("Waiting for players to get ready ({}/{} ready)", ready, needed));
+                return;
+            }
+        }
     } else {
-        None
+        room.connected_players()
+    };
+    if players.len() < room.min_players {
+        session.send_error("not_enough_players", format!("Need at least {} players to start", room.min_players));
+        return;
+    }
+    // The room's match format applies unless `start` describes the match itself
+    let (format, mode) = match (format, mode) {
+        (None, None) => (room.format, room.mode),
+        (format, mode) => (format.unwrap_or_default(), mode.unwrap_or_default()),
+    };
+    if let Err(err) = start_match(&session.state, &session.room_id, room, players, format, mode) {
+        session.send_error(err.code(), err.to_string());
+    }
+}
+
+// Start a match; readiness is used up by it
+fn start_match(state: &SharedState, room_id: &str, room: &mut Room, players: Vec<Uuid>, format: MatchFormat, mode: ScoringMode) -> Result<(), GameError> {
+    game_loop::apply_command(state, room_id, room, Command::Start { players, format, mode })?;
+    room.ready.clear();
+    Ok(())
+}
+
+// Ready-check rooms: start the next match (in the room's match format) once every connected player is ready
+fn start_when_ready(state: &SharedState, room_id: &str, room: &mut Room) {
+    if !room.game.settings().ready_check || room.game.is_active() {
+        return;
+    }
+    let Ok(ready) = room.ready_to_start(None) else { return };
+    let (format, mode) = (room.format, room.mode);
+    let _ = start_match(state, room_id, room, ready, format, mode);
+}
+
+async fn set_ready(session: &Session, ready: bool) {
+    let mut app = session.state.lock().await;
+    let Some(room) = app.rooms.get_mut(&session.room_id) else { return };
+    if !room.game.settings().ready_check {
+        session.send_error("ready_check_disabled", "This room has no ready-check");
+        return;
+    }
+    if room.game.is_active() {
+        session.send_error("game_in_progress", "Readiness can only change between matches");
+        return;
+    }
+    let changed = if ready { room.ready.insert(session.client_id) } else { room.ready.remove(&session.client_id) };
+    if changed {
+        room.broadcast(&ServerEvent::PlayerReady(PlayerReadyResponse { room_id: session.room_id.clone(), player: room.player_ref(&session.client_id), ready }));
+        start_when_ready(&session.state, &session.room_id, room);
+    }
+}
+
+async fn submit_move(session: &Session, choice: String) {
+    let mut app = session.state.lock().await;
+    let Some(room) = app.rooms.get_mut(&session.room_id) else { return };
+    run_command(session, room, Command::Move { player: session.client_id, choice });
+}
+
+async fn lock_in(session: &Session) {
+    let mut app = session.state.lock().await;
+    let Some(room) = app.rooms.get_mut(&session.room_id) else { return };
+    run_command(session, room, Command::LockIn { player: session.client_id });
+}
+
+async fn commit_move(session: &Session, hash: String) {
+    let mut app = session.state.lock().await;
+    let Some(room) = app.rooms.get_mut(&session.room_id) else { return };
+    run_command(session, room, Command::Commit { player: session.client_id, hash });
+}
+
+async fn reveal_move(session: &Session, choice: String, nonce: String) {
+    let mut app = session.state.lock().await;
+    let Some(room) = app.rooms.get_mut(&session.room_id) else { return };
+    run_command(session, room, Command::Reveal { player: session.client_id, choice, nonce });
+}
+
+async fn update_settings(session: &Session, settings: GameSettings) {
+    let mut app = session.state.lock().await;
+    let Some(room) = app.rooms.get_mut(&session.room_id) else { return };
+    if !is_host(session, room) {
+        return;
+    }
+    run_command(session, room, Command::UpdateSettings { settings });
+}
+
+// Host only: resize the room between matches; omitted limits stay as they are
+async fn update_limits(session: &Session, capacity: Option<usize>, min_players: Option<usize>) {
+    let mut app = session.state.lock().await;
+    let config = app.config.clone();
+    let Some(room) = app.rooms.get_mut(&session.room_id) else { return };
+    if !is_host(session, room) {
+        return;
+    }
+    if room.game.is_active() {
+        session.send_error("game_in_progress", "Room limits can only change between matches");
+        return;
+    }
+    let (capacity, min_players) = (capacity.unwrap_or(room.capacity), min_players.unwrap_or(room.min_players));
+    if let Err(err) = config.check_room_limits(capacity, min_players) {
+        session.send_error("invalid_limits", err);
+        return;
+    }
+    if capacity < room.clients.len() {
+        session.send_error("invalid_limits", format!("capacity is below the {} players already in the room", room.clients.len()));
+        return;
+    }
+    room.capacity = capacity;
+    room.min_players = min_players;
+    room.broadcast(&ServerEvent::LimitsUpdated(LimitsUpdatedResponse { room_id: session.room_id.clone(), capacity, min_players }));
+    app.broadcast_room_list();
+}
+
+// Host only: close the room to new players, or open it again
+async fn set_locked(session: &Session, locked: bool) {
+    let mut app = session.state.lock().await;
+    let Some(room) = app.rooms.get_mut(&session.room_id) else { return };
+    if !is_host(session, room) {
+        return;
+    }
+    if room.locked != locked {
+        room.locked = locked;
+        room.broadcast(&ServerEvent::RoomLocked(RoomLockedResponse { room_id: session.room_id.clone(), locked }));
+        app.broadcast_room_list();
+    }
+}
+
+// Whether the sender runs this room; tells them off if not
+fn is_host(session: &Session, room: &Room) -> bool {
+    if room.host != Some(session.client_id) {
+        session.send_error("not_host", "Only the host can do that");
+        return false;
+    }
+    true
+}
+
+// Feed a command to the room's game, reporting a rejection to the sender
+fn run_command(session: &Session, room: &mut Room, command: Command) {
+    if let Err(err) = game_loop::apply_command(&session.state, &session.room_id, room, command) {
+        session.send_error(err.code(), err.to_string());
+    }
+}
+
+// Post a chat message to all clients in this room and its history
+async fn relay_chat(session: &Session, message: String) {
+    let mut app = session.state.lock().await;
+    let config = app.config.clone();
+    if message.chars().count() > config.max_chat_length {
+        session.send_error("message_too_long", format!("Chat message too long (max {} characters)", config.max_chat_length));
+        return;
+    }
+    let Some(room) = app.rooms.get_mut(&session.room_id) else { return };
+    if room.muted.contains(&session.client_id) {
+        session.send_error("muted", "You have been muted by the host");
+        return;
+    }
+    let Some(client) = room.clients.get_mut(&session.client_id) else { return };
+    let window = Duration::from_secs(config.chat_rate_window_secs);
+    if let Err(wait) = moderation::check_rate(&mut client.chat_sent, Instant::now(), config.chat_rate_limit, window) {
+        session.send_error("rate_limited", format!("Too many chat messages, try again in {} s", wait.as_millis().div_ceil(1000)));
+        return;
+    }
+    let message = moderation::mask_blocked_words(&message, &config.blocked_words);
+    let chat = room.post_chat(&session.room_id, &session.client_id, message, config.chat_history_len);
+    room.broadcast(&ServerEvent::ChatMessage(chat));
+}
+
+// Host only: mute or unmute another player in the room
+async fn set_muted(session: &Session, player: &str, muted: bool) {
+    let mut app = session.state.lock().await;
+    let Some(room) = app.rooms.get_mut(&session.room_id) else { return };
+    if !is_host(session, room) {
+        return;
+    }
+    let Some(player) = target_player(session, room, player, "mute") else { return };
+    let changed = if muted { room.muted.insert(player) } else { room.muted.remove(&player) };
+    if changed {
+        room.broadcast(&ServerEvent::PlayerMuted(PlayerMutedResponse { room_id: session.room_id.clone(), player: room.player_ref(&player), muted }));
+    }
+}
+
+// Host only: remove another player from the room, optionally banning their seat for a while
+async fn kick_player(session: &Session, player: &str, ban_secs: Option<u64>) {
+    let mut app = session.state.lock().await;
+    let ban_addresses = app.config.ban_addresses;
+    let Some(room) = app.rooms.get_mut(&session.room_id) else { return };
+    if !is_host(session, room) {
+        return;
+    }
+    // A kicked seat is gone for good, so a ban can only keep the player out by their address
+    if ban_secs.is_some() && !ban_addresses {
+        session.send_error("bans_disabled", "This server does not ban players, kick without ban_secs");
+        return;
+    }
+    let Some(player) = target_player(session, room, player, "kick") else { return };
+    let ban = ban_secs.map(Duration::from_secs);
+    let kicked = KickedResponse { room_id: session.room_id.clone(), player: room.player_ref(&player), banned_until_ms: ban.map(|ban| game_loop::unix_millis(SystemTime::now() + ban)) };
+    room.broadcast(&ServerEvent::Kicked(kicked));
+    let Some(client) = room.clients.get(&player) else { return };
+    if let Some(ban) = ban {
+        room.bans.insert(player, Ban { address: client.address, until: Instant::now() + ban });
+    }
+    // The kicked connection is closed once the `kicked` event has gone out; the seat is given up like on `leave`
+    client.close.notify_one();
+    remove_client(&session.state, &mut app, &session.room_id, player);
+}
+
+// Resolve the target of a host action: another player in the room
+fn target_player(session: &Session, room: &Room, player: &str, verb: &str) -> Option<Uuid> {
+    let Some(player) = Uuid::parse_str(player).ok().filter(|id| room.clients.contains_key(id)) else {
+        session.send_error("unknown_player", "No such player in this room");
+        return None;
+    };
+    if player == session.client_id {
+        session.send_error("invalid_target", format!("You cannot {} yourself", verb));
+        return None;
+    }
+    Some(player)
+}
+
+// Bring a player who just joined (or rejoined) up to date: room snapshot, then recent chat
+fn send_catch_up(room: &Room, room_id: &str, client_id: Uuid) {
+    room.send_to(&client_id, &room.state(room_id, client_id));
+    let messages = room.chat_history.iter().cloned().collect();
+    room.send_to(&client_id, &ServerEvent::ChatHistory(ChatHistoryResponse { room_id: room_id.to_string(), messages }));
+}
+
+// Query parameters accepted by `/join/{room_id}`; rule and settings options only apply when the join creates the room
+#[derive(Debug, Default, Deserialize)]
+pub struct JoinParams {
+    pub rules: Option<RuleVariant>,
+    // Comma-separated move list for the `balanced` variant
+    pub moves: Option<String>,
+    // JSON `RuleDefinition` for the `custom` variant
+    pub definition: Option<String>,
+    pub round_timeout_secs: Option<u64>,
+    pub timeout_policy: Option<TimeoutPolicy>,
+    pub countdown_secs: Option<u64>,
+    pub submission_window_secs: Option<u64>,
+    pub commit_reveal: Option<bool>,
+    pub move_changes: Option<MoveChangePolicy>,
+    pub ready_check: Option<bool>,
+    pub ready_quorum: Option<usize>,
+    pub capacity: Option<usize>,
+    pub min_players: Option<usize>,
+    // Leave the room out of room lists
+    pub private: Option<bool>,
+    // Password of a protected room; a join that creates the room protects it with this password
+    pub password: Option<String>,
+    // Reconnect to a held seat instead of joining as a new player
+    pub resume_token: Option<String>,
+    // Display profile; can also be set (or changed) later with a `hello` action
+    pub name: Option<String>,
+    pub avatar: Option<String>,
+}
+
+impl JoinParams {
+    fn settings(&self) -> Result<GameSettings, String> {
+        let settings = GameSettings {
+            round_timeout_secs: self.round_timeout_secs,
+            timeout_policy: self.timeout_policy.unwrap_or_default(),
+            countdown_secs: self.countdown_secs,
+            submission_window_secs: self.submission_window_secs,
+            commit_reveal: self.commit_reveal.unwrap_or(false),
+            move_changes: self.move_changes.unwrap_or_default(),
+            ready_check: self.ready_check.unwrap_or(false),
+            ready_quorum: self.ready_quorum,
+        };
+        settings.validate()?;
+        Ok(settings)
+    }
+
+    // Validated (name, avatar) for the joining player
+    fn profile(&self) -> Result<(Option<String>, Option<String>), String> {
+        let name = self.name.as_deref().map(validate_name).transpose()?;
+        let avatar = self.avatar.as_deref().map(validate_avatar).transpose()?;
+        Ok((name, avatar))
+    }
+
+    // The room this join creates if the room does not exist yet
+    fn new_room(&self, config: &ServerConfig) -> Result<Room, String> {
+        let rules = self.rule_set().map_err(|err| format!("Invalid rules: {}", err))?;
+        let settings = self.settings().map_err(|err| format!("Invalid settings: {}", err))?;
+        let (capacity, min_players) = (self.capacity.unwrap_or(config.max_room_capacity), self.min_players.unwrap_or(MIN_PLAYERS));
+        config.check_room_limits(capacity, min_players).map_err(|err| format!("Invalid room limits: {}", err))?;
+        let password = self.password.as_deref().map(RoomPassword::new).transpose().map_err(|err| format!("Invalid password: {}", err))?;
+        let mut room = Room::new(Game::with_rules(rules, settings), capacity, min_players);
+        room.private = self.private.unwrap_or(false);
+        room.password = password;
+        Ok(room)
+    }
+
+    fn rule_set(&self) -> Result<RuleSet, String> {
+        let variant = self.rules.unwrap_or_default();
+        if variant == RuleVariant::Custom {
+            let json = self.definition.as_deref().ok_or_else(|| RuleError::MissingDefinition.to_string())?;
+            let definition: RuleDefinition = serde_json::from_str(json).map_err(|e| format!("Invalid rule definition: {}", e))?;
+            return RuleSet::from_definition(definition).map_err(|e| e.to_string());
+        }
+        let moves = self.moves.as_ref().map(|list| list.split(',').map(str::to_string).collect());
+        RuleSet::from_variant(variant, moves).map_err(|e| e.to_string())
+    }
+}
+
+pub async fn join_room(Path(room_id): Path<String>, Query(params): Query<JoinParams>, ConnectInfo(peer): ConnectInfo<SocketAddr>, headers: HeaderMap, ws: WebSocketUpgrade, State(state): State<SharedState>) -> Response {
+    let app = state.lock().await;
+    // A wrong or missing password is answered over HTTP, before the socket is opened; resume tokens need none
+    if params.resume_token.is_none() {
+        if let Some(Err((code, message))) = app.rooms.get(&room_id).map(|room| room.check_password(params.password.as_deref())) {
+            return ErrorResponse::http(StatusCode::FORBIDDEN, Some(room_id), code, message);
+        }
+    }
+    let address = app.config.client_address(&headers, peer.ip());
+    drop(app);
+    ws.on_upgrade(move |socket| handle_join_room(room_id, params, address, socket, state)).into_response()
+}
+
+// Tell a client its join was refused and close the connection
+async fn reject_join(sender: &mut SplitSink<WebSocket, Message>, room_id: &str, client_id: Uuid, message: String) {
+    let response = JoinRoomResponse {
+        success: false,
+        room_id: Some(room_id.to_string()),
+        message: Some(message),
+        my_id: Some(client_id.to_string()),
+        player: None,
+        resume_token: None,
     };
+    let _ = sender.send(Message::Text(ServerEvent::Join(response).to_json().into())).await;
+    let _ = sender.send(Message::Close(None)).await;
+}
 
-    if let Some(win) = winning_move {
-        let mut winners: Vec<Uuid> = vec![];
-        for pid in active_players {
-            if let Some(mv) = moves.get(pid) { if mv == win { winners.push(*pid); } }
+// Seat a new client in the room, creating the room (with the joiner's rules and settings) if needed; `new_room` is
+// the room built for a join that found none
+fn take_seat(app: &mut AppState, room_id: &str, params: &JoinParams, new_room: Option<Result<Room, String>>, client_id: Uuid, seat: ClientEntry) -> Result<(), String> {
+    let room = match app.rooms.entry(room_id.to_string()) {
+        Entry::Occupied(entry) => {
+            // Checked again here in case the room was created after the HTTP-level check
+            let room = entry.into_mut();
+            room.check_password(params.password.as_deref()).map_err(|(_, message)| message.to_string())?;
+            room
         }
-        if winners.len() == 1 {
-            Outcome::SingleWinner { winner: winners[0], moves: moves_map }
-        } else {
-            Outcome::MultiWinners { winners, moves: moves_map }
+        Entry::Vacant(_) if !app.config.implicit_rooms => return Err(format!("Room {} does not exist", room_id)),
+        // The first joiner creates the room and picks its rule set and settings; a room that went away since it was looked up is built here
+        Entry::Vacant(entry) => entry.insert(new_room.unwrap_or_else(|| params.new_room(&app.config))?),
+    };
+    // New players get a fresh id, so a kicked player joining again is recognised by their address
+    if let Some(left) = room.ban_remaining(Instant::now(), |ban| ban.address == seat.address) {
+        return Err(banned(left));
+    }
+    if room.locked {
+        return Err("Room is locked".to_string());
+    }
+    if room.clients.len() >= room.capacity {
+        // Room is full: inform client with JSON and close connection
+        return Err(format!("Room is full (max {} players)", room.capacity));
+    }
+    let resume_token = seat.resume_token.clone();
+    room.clients.insert(client_id, seat);
+    // The first player into a room runs it
+    room.host.get_or_insert(client_id);
+    let player = room.player_ref(&client_id);
+
+    // Broadcast join message to all clients in room as JSON (include recipient's my_id, and the joiner's resume token)
+    for (id, client) in room.clients.iter() {
+        let response = JoinRoomResponse {
+            success: true,
+            room_id: Some(room_id.to_string()),
+            message: Some(format!("{} joined room {}", player.name, room_id)),
+            my_id: Some(id.to_string()),
+            player: Some(player.clone()),
+            resume_token: (*id == client_id).then(|| resume_token.clone()),
+        };
+        let _ = client.tx.send(ServerEvent::Join(response).to_json());
+    }
+    send_catch_up(room, room_id, client_id);
+
+    // Notify room watchers about updated rooms list
+    app.broadcast_room_list();
+    Ok(())
+}
+
+// Why a banned player's join is refused
+fn banned(left: Duration) -> String {
+    format!("You are banned from this room for another {} s", left.as_millis().div_ceil(1000))
+}
+
+// Hand a reserved seat back to the client holding its resume token; returns the seat's player id
+fn resume_seat(app: &mut AppState, room_id: &str, token: &str, seat: ClientEntry) -> Result<Uuid, String> {
+    let invalid = || "Invalid or expired resume token".to_string();
+    let room = app.rooms.get_mut(room_id).ok_or_else(invalid)?;
+    let (client_id, client) = room.clients.iter_mut().find(|(_, client)| client.resume_token == token).ok_or_else(invalid)?;
+    let client_id = *client_id;
+    // A connection still holding the seat (e.g. a half-open socket) is told to stop
+    client.close.notify_one();
+    *client = ClientEntry { resume_token: client.resume_token.clone(), name: client.name.take(), avatar: client.avatar.take(), chat_sent: std::mem::take(&mut client.chat_sent), joined_at: client.joined_at, ..seat };
+    let tx = client.tx.clone();
+
+    let player = room.player_ref(&client_id);
+    let response = JoinRoomResponse {
+        success: true,
+        room_id: Some(room_id.to_string()),
+        message: Some(format!("{} rejoined room {}", player.name, room_id)),
+        my_id: Some(client_id.to_string()),
+        player: Some(player),
+        resume_token: Some(token.to_string()),
+    };
+    let _ = tx.send(ServerEvent::Join(response).to_json());
+    send_catch_up(room, room_id, client_id);
+    room.broadcast(&ServerEvent::PlayerReconnected(PlayerReconnectedResponse { room_id: room_id.to_string(), player: room.player_ref(&client_id) }));
+    // A room whose host was away with everyone else gets a host again
+    migrate_host(room, room_id);
+    Ok(client_id)
+}
+
+// Give up a seat for good: the player forfeits, and the room goes away once it is empty
+fn remove_client(state: &SharedState, app: &mut AppState, room_id: &str, client_id: Uuid) {
+    if let Some(room) = app.rooms.get_mut(room_id) {
+        let player = room.player_ref(&client_id);
+        println!("Client {:?} left room {}", client_id, room_id);
+        for (id, client) in room.clients.iter().filter(|(id, _)| **id != client_id) {
+            let response = JoinRoomResponse {
+                success: true,
+                room_id: Some(room_id.to_string()),
+                message: Some(format!("{} left room {}", player.name, room_id)),
+                my_id: Some(id.to_string()),
+                player: Some(player.clone()),
+                resume_token: None,
+            };
+            let _ = client.tx.send(ServerEvent::Leave(response).to_json());
+        }
+        // The leaving player forfeits, while still seated so events name them; the game carries on with everyone else
+        let _ = game_loop::apply_command(state, room_id, room, Command::Leave { player: client_id });
+        room.clients.remove(&client_id);
+        room.muted.remove(&client_id);
+        room.ready.remove(&client_id);
+        migrate_host(room, room_id);
+        // Everyone still here may have been waiting only for the player who left
+        start_when_ready(state, room_id, room);
+        // Remove room entirely if empty
+        if room.clients.is_empty() {
+            app.rooms.remove(room_id);
         }
-    } else {
-        Outcome::Tie { moves: moves_map }
     }
+    // Notify room watchers about updated rooms list
+    app.broadcast_room_list();
 }
 
-pub async fn join_room(Path(room_id): Path<String>, ws: WebSocketUpgrade, State(state): State<crate::server::server::SharedState>) -> impl IntoResponse {
-    ws.on_upgrade(move |socket| handle_join_room(room_id, socket, state))
+// A host who left or lost their connection hands the room over to whoever has been here longest, preferring connected players
+fn migrate_host(room: &mut Room, room_id: &str) {
+    if room.host.and_then(|host| room.clients.get(&host)).is_some_and(|client| client.connected) {
+        return;
+    }
+    let next = room.next_host();
+    if next == room.host {
+        return;
+    }
+    room.host = next;
+    if let Some(host) = next {
+        room.broadcast(&ServerEvent::HostChanged(HostChangedResponse { room_id: room_id.to_string(), host: room.player_ref(&host) }));
+    }
 }
 
 // Handle the actual WebSocket connection
-async fn handle_join_room(room_id: String, socket: WebSocket, state: crate::server::server::SharedState) {
+async fn handle_join_room(room_id: String, params: JoinParams, address: IpAddr, socket: WebSocket, state: SharedState) {
     let (mut sender, mut receiver) = socket.split();
 
     // Create a channel to send messages to this client
     let (tx, mut rx) = mpsc::unbounded_channel::<String>();
-    let client_id = Uuid::new_v4();
-
-    // Validate and add client to the specified room
-    {
-        let mut app = state.lock().await;
-        let room = app.rooms.entry(room_id.clone()).or_insert_with(|| crate::server::server::Room {
-            clients: HashMap::new(),
-            game_active: false,
-            moves: HashMap::new(),
-            active_players: HashSet::new(),
-        });
-        if room.clients.len() >= MAX_PLAYERS_PER_ROOM {
-            // Room is full: inform client with JSON and close connection
-            let response = JoinRoomResponse {
-                success: false,
-                room_id: Some(room_id.clone()),
-                message: Some(format!("Room is full (max {} players)", MAX_PLAYERS_PER_ROOM).into()),
-                my_id: Some(client_id.to_string()),
-            };
-            if let Ok(json) = serde_json::to_string(&response) {
-                let _ = sender.send(Message::Text(json.into())).await;
-            }
-            let _ = sender.send(Message::Close(None)).await;
+    let connection = Uuid::new_v4();
+    let close = Arc::new(Notify::new());
+    let (name, avatar) = match params.profile() {
+        Ok(profile) => profile,
+        Err(err) => {
+            reject_join(&mut sender, &room_id, Uuid::new_v4(), format!("Invalid profile: {}", err)).await;
             return;
         }
-        room.clients.insert(client_id, tx.clone());
+    };
+    let seat = ClientEntry { tx: tx.clone(), resume_token: Uuid::new_v4().simple().to_string(), name, avatar, connection, address, connected: true, close: close.clone(), chat_sent: VecDeque::new(), joined_at: Instant::now() };
 
-        // Broadcast join message to all clients in room as JSON (include recipient's my_id)
-        for (id, client_tx) in room.clients.iter() {
-            let response = JoinRoomResponse {
-                success: true,
-                room_id: Some(room_id.clone()),
-                message: Some(format!("Client {:?} joined room {}", client_id, room_id).into()),
-                my_id: Some(id.to_string()),
+    // Validate and add client to the specified room, or give a reserved seat back to its owner
+    let new_id = Uuid::new_v4();
+    let seated = match params.resume_token.as_deref() {
+        Some(token) => resume_seat(&mut *state.lock().await, &room_id, token, seat),
+        None => {
+            // Only a join that finds no room builds one, outside the state lock as `POST /rooms` does
+            let config = {
+                let app = state.lock().await;
+                (!app.rooms.contains_key(&room_id)).then(|| app.config.clone())
             };
-            if let Ok(json) = serde_json::to_string(&response) {
-                let _ = client_tx.send(json);
-            }
+            let new_room = config.map(|config| params.new_room(&config));
+            take_seat(&mut *state.lock().await, &room_id, &params, new_room, new_id, seat).map(|()| new_id)
         }
-
-        // Notify room watchers about updated rooms list
-        let rooms_snapshot: Vec<RoomInfo> = app.rooms.iter().map(|(rid, room)| RoomInfo { room_id: rid.clone(), client_count: room.clients.len() }).collect();
-        let payload = serde_json::to_string(&RoomListResponse { rooms: rooms_snapshot }).unwrap_or_else(|_| "{}".into());
-        for (_wid, watcher_tx) in app.room_watchers.iter() {
-            let _ = watcher_tx.send(payload.clone());
+    };
+    let client_id = match seated {
+        Ok(client_id) => client_id,
+        Err(message) => {
+            reject_join(&mut sender, &room_id, new_id, message).await;
+            return;
         }
-    }
+    };
 
     println!("Client {:?} joined room {}", client_id, room_id);
 
-    // Task to forward messages from room to client
-    let send_task = tokio::spawn(async move {
-        while let Some(msg) = rx.recv().await {
-            if sender.send(Message::Text(msg.into())).await.is_err() {
-                break;
+    // Task to forward messages from room to client, until the seat tells this connection to close
+    let mut send_task = tokio::spawn(async move {
+        loop {
+            tokio::select! {
+                // Queued messages (e.g. a `kicked` event) go out before the close frame
+                biased;
+                msg = rx.recv() => {
+                    let Some(msg) = msg else { break };
+                    if sender.send(Message::Text(msg.into())).await.is_err() {
+                        break;
+                    }
+                }
+                _ = close.notified() => {
+                    let _ = sender.send(Message::Close(None)).await;
+                    break;
+                }
             }
         }
     });
 
-    // Task to receive messages from this client
-    let state_clone = state.clone();
-    let room_id_clone = room_id.clone();
-    let my_id_clone = client_id;
-    let my_tx = tx.clone();
-    let receive_task = tokio::spawn(async move {
+    // Task to receive messages from this client; yields true when the client left on purpose
+    let session = Session { state: state.clone(), room_id: room_id.clone(), client_id, tx: tx.clone() };
+    let mut receive_task = tokio::spawn(async move {
         while let Some(Ok(msg)) = receiver.next().await {
             if let Message::Text(text) = msg {
-                // Try to parse as JSON command
-                let maybe_val: Result<serde_json::Value, _> = serde_json::from_str(&text);
-                if let Ok(val) = maybe_val {
-                    let action = val.get("action").and_then(|v| v.as_str()).unwrap_or("").to_lowercase();
-                    match action.as_str() {
-                        "start" | "start_game" => {
-                            let mut app = state_clone.lock().await;
-                            if let Some(room) = app.rooms.get_mut(&room_id_clone) {
-                                if room.game_active {
-                                    let err = ErrorResponse { event: "error", room_id: Some(room_id_clone.clone()), message: "Game already active".into(), my_id: Some(my_id_clone.to_string()) };
-                                    if let Ok(json) = serde_json::to_string(&err) { let _ = my_tx.send(json); }
-                                } else if room.clients.len() < 2 {
-                                    let err = ErrorResponse { event: "error", room_id: Some(room_id_clone.clone()), message: "Need at least 2 players to start".into(), my_id: Some(my_id_clone.to_string()) };
-                                    if let Ok(json) = serde_json::to_string(&err) { let _ = my_tx.send(json); }
-                                } else {
-                                    room.game_active = true;
-                                    room.moves.clear();
-                                    room.active_players = room.clients.keys().cloned().collect();
-                                    // Snapshot current active players
-                                    let players: Vec<String> = room.active_players.iter().map(|id| id.to_string()).collect();
-                                    let start_msg = GameStartedResponse { event: "game_started", room_id: room_id_clone.clone(), players };
-                                    if let Ok(json) = serde_json::to_string(&start_msg) {
-                                        for (_id, client_tx) in room.clients.iter() { let _ = client_tx.send(json.clone()); }
-                                    }
-                                }
-                            }
-                        }
-                        "move" => {
-                            let choice = val.get("choice").and_then(|v| v.as_str()).unwrap_or("").to_lowercase();
-                            if !matches!(choice.as_str(), "rock" | "paper" | "scissors") {
-                                let err = ErrorResponse { event: "error", room_id: Some(room_id_clone.clone()), message: "Invalid choice, use rock|paper|scissors".into(), my_id: Some(my_id_clone.to_string()) };
-                                if let Ok(json) = serde_json::to_string(&err) { let _ = my_tx.send(json); }
-                                continue;
-                            }
-                            {
-                                let mut app = state_clone.lock().await;
-                                if let Some(room) = app.rooms.get_mut(&room_id_clone) {
-                                    if !room.game_active {
-                                        let err = ErrorResponse { event: "error", room_id: Some(room_id_clone.clone()), message: "Game not active".into(), my_id: Some(my_id_clone.to_string()) };
-                                        if let Ok(json) = serde_json::to_string(&err) { let _ = my_tx.send(json); }
-                                    } else if !room.active_players.contains(&my_id_clone) {
-                                        let err = ErrorResponse { event: "error", room_id: Some(room_id_clone.clone()), message: "You are not active in this round".into(), my_id: Some(my_id_clone.to_string()) };
-                                        if let Ok(json) = serde_json::to_string(&err) { let _ = my_tx.send(json); }
-                                    } else {
-                                        room.moves.insert(my_id_clone, choice.clone());
-                                        // If all active players submitted, compute outcome
-                                        if room.moves.len() == room.active_players.len() {
-                                            match compute_round_outcome(&room.active_players, &room.moves) {
-                                                Outcome::Tie { moves } => {
-                                                    // Rematch with same active players
-                                                    let next_players: Vec<String> = room.active_players.iter().map(|id| id.to_string()).collect();
-                                                    let rem = RematchResponse { event: "rematch", room_id: room_id_clone.clone(), next_players, reason: "tie_all".into(), moves };
-                                                    if let Ok(json) = serde_json::to_string(&rem) {
-                                                        for (_id, client_tx) in room.clients.iter() { let _ = client_tx.send(json.clone()); }
-                                                    }
-                                                    room.moves.clear();
-                                                    // keep game_active and active_players as-is
-                                                }
-                                                Outcome::MultiWinners { winners, moves } => {
-                                                    // Only winners continue
-                                                    let next_players: Vec<String> = winners.iter().map(|id| id.to_string()).collect();
-                                                    let rem = RematchResponse { event: "rematch", room_id: room_id_clone.clone(), next_players: next_players.clone(), reason: "multiple_winners".into(), moves };
-                                                    if let Ok(json) = serde_json::to_string(&rem) {
-                                                        for (_id, client_tx) in room.clients.iter() { let _ = client_tx.send(json.clone()); }
-                                                    }
-                                                    room.active_players = winners.into_iter().collect();
-                                                    room.moves.clear();
-                                                }
-                                                Outcome::SingleWinner { winner, moves } => {
-                                                    let result = RoundResultResponse { event: "round_result", room_id: room_id_clone.clone(), tie: false, winners: vec![winner.to_string()], moves };
-                                                    if let Ok(json) = serde_json::to_string(&result) {
-                                                        for (_id, client_tx) in room.clients.iter() { let _ = client_tx.send(json.clone()); }
-                                                    }
-                                                    // End game
-                                                    room.game_active = false;
-                                                    room.active_players.clear();
-                                                    room.moves.clear();
-                                                }
-                                            }
-                                        }
-                                    }
-                                }
-                            }
-                            // Optionally could broadcast acknowledgement, but result broadcast covers end of (sub)round
-                        }
-                        _ => {
-                            // Unknown action; ignore or echo
-                            let err = ErrorResponse { event: "error", room_id: Some(room_id_clone.clone()), message: "Unknown action".into(), my_id: Some(my_id_clone.to_string()) };
-                            if let Ok(json) = serde_json::to_string(&err) { let _ = my_tx.send(json); }
+                match ClientAction::parse(&text) {
+                    Ok(action) => {
+                        if !dispatch(&session, action).await {
+                            return true;
                         }
                     }
-                    continue;
-                }
-
-                // Default: Broadcast plain text to all clients in this room only
-                let app = state_clone.lock().await;
-                if let Some(room) = app.rooms.get(&room_id_clone) {
-                    for (_, client_tx) in room.clients.iter() {
-                        let _ = client_tx.send(text.to_string());
-                    }
+                    Err(message) => session.send_error("invalid_message", message),
                 }
             }
         }
+        false
     });
 
-    // Wait for either task to complete (disconnect)
-    tokio::select! {
-        _ = send_task => {},
-        _ = receive_task => {},
+    // Wait for either task to complete: a disconnect, or the seat closing this connection
+    let left = tokio::select! {
+        _ = &mut send_task => false,
+        left = &mut receive_task => left.unwrap_or(false),
+    };
+    send_task.abort();
+    receive_task.abort();
+
+    let mut app = state.lock().await;
+    let Some(room) = app.rooms.get_mut(&room_id) else { return };
+    // A kicked player's seat is already gone
+    let Some(client) = room.clients.get_mut(&client_id) else { return };
+    if client.connection != connection {
+        // A newer connection holds the seat now
+        return;
+    }
+    if left {
+        remove_client(&state, &mut app, &room_id, client_id);
+        return;
     }
 
-    // Remove client from room on disconnect
-    {
+    // Dropped connection: hold the seat so the player can come back with their resume token
+    client.connected = false;
+    let grace = Duration::from_secs(RESUME_GRACE_SECS);
+    room.broadcast(&ServerEvent::PlayerDisconnected(PlayerDisconnectedResponse {
+        room_id: room_id.clone(),
+        player: room.player_ref(&client_id),
+        reconnect_deadline_ms: game_loop::unix_millis(SystemTime::now() + grace),
+    }));
+    migrate_host(room, &room_id);
+    // Everyone still connected may have been waiting only for the player who dropped
+    start_when_ready(&state, &room_id, room);
+    println!("Client {:?} disconnected from room {}", client_id, room_id);
+    drop(app);
+
+    tokio::spawn(async move {
+        tokio::time::sleep(grace).await;
         let mut app = state.lock().await;
-        if let Some(room) = app.rooms.get_mut(&room_id) {
-            room.clients.remove(&client_id);
-            println!("Client {:?} left room {}", client_id, room_id);
-            for (id, client_tx) in room.clients.iter() {
-                let response = JoinRoomResponse {
-                    success: true,
-                    room_id: Some(room_id.clone()),
-                    message: Some(format!("Client {:?} left room {}", client_id, room_id)),
-                    my_id: Some(id.to_string()),
-                };
-                if let Ok(json) = serde_json::to_string(&response) {
-                    let _ = client_tx.send(json);
-                }
-            }
-            // If game was active and a player leaves, end the game
-            if room.game_active {
-                room.game_active = false;
-                room.moves.clear();
-                room.active_players.clear();
-            }
-            // Remove room entirely if empty
-            if room.clients.is_empty() {
-                app.rooms.remove(&room_id);
-            }
+        let still_away = app.rooms.get(&room_id).and_then(|room| room.clients.get(&client_id)).is_some_and(|client| client.connection == connection);
+        if still_away {
+            remove_client(&state, &mut app, &room_id, client_id);
         }
-        // Notify room watchers about updated rooms list
-        let rooms_snapshot: Vec<RoomInfo> = app.rooms.iter().map(|(rid, room)| RoomInfo { room_id: rid.clone(), client_count: room.clients.len() }).collect();
-        let payload = serde_json::to_string(&RoomListResponse { rooms: rooms_snapshot }).unwrap_or_else(|_| "{}".into());
-        for (_wid, watcher_tx) in app.room_watchers.iter() {
-            let _ = watcher_tx.send(payload.clone());
-        }
-    }
+    });
 }
-
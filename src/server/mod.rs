@@ -1,4 +1,10 @@
+#[allow(clippy::module_inception)]
 pub mod server;
 pub mod join_room;
+pub mod create_room;
 pub mod rooms_stream;
 pub mod responses;
+pub mod actions;
+pub mod game_loop;
+pub mod moderation;
+pub mod password;
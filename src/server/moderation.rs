@@ -0,0 +1,95 @@
+use std::collections::{HashSet, VecDeque};
+use std::time::{Duration, Instant};
+
+// Replace every blocked word (matched case-insensitively, as a whole word) with asterisks of the same length
+pub fn mask_blocked_words(message: &str, blocked: &HashSet<String>) -> String {
+    if blocked.is_empty() {
+        return message.to_string();
+    }
+    let mut masked = String::with_capacity(message.len());
+    let mut word = String::new();
+    for c in message.chars().chain(std::iter::once(' ')) {
+        if c.is_alphanumeric() {
+            word.push(c);
+            continue;
+        }
+        if blocked.contains(&word.to_lowercase()) {
+            masked.extend(std::iter::repeat_n('*', word.chars().count()));
+        } else {
+            masked.push_str(&word);
+        }
+        word.clear();
+        masked.push(c);
+    }
+    // Drop the sentinel space
+    masked.pop();
+    masked
+}
+
+// Sliding-window rate limit: at most `limit` messages in any `window`. `sent` holds the times of
+// recent messages; on success `now` is recorded, otherwise the wait until the next message is allowed is returned.
+pub fn check_rate(sent: &mut VecDeque<Instant>, now: Instant, limit: usize, window: Duration) -> Result<(), Duration> {
+    while sent.front().is_some_and(|first| now.duration_since(*first) >= window) {
+        sent.pop_front();
+    }
+    if let Some(first) = sent.front().filter(|_| sent.len() >= limit) {
+        return Err(window - now.duration_since(*first));
+    }
+    sent.push_back(now);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn blocked(words: &[&str]) -> HashSet<String> {
+        words.iter().map(|word| word.to_string()).collect()
+    }
+
+    #[test]
+    fn blocked_words_are_masked_case_insensitively() {
+        let words = blocked(&["darn", "heck"]);
+        assert_eq!(mask_blocked_words("Darn it, what the HECK!", &words), "**** it, what the ****!");
+        assert_eq!(mask_blocked_words("darn", &words), "****");
+        assert_eq!(mask_blocked_words("  darn  ", &words), "  ****  ");
+    }
+
+    #[test]
+    fn only_whole_words_are_masked() {
+        let words = blocked(&["ass"]);
+        assert_eq!(mask_blocked_words("pass the class, ass", &words), "pass the class, ***");
+        assert_eq!(mask_blocked_words("ass2 ass_", &words), "ass2 ***_");
+    }
+
+    #[test]
+    fn masks_keep_the_length_in_characters() {
+        let words = blocked(&["größe"]);
+        assert_eq!(mask_blocked_words("Größe zählt", &words), "***** zählt");
+        assert_eq!(mask_blocked_words("anything goes", &HashSet::new()), "anything goes");
+    }
+
+    #[test]
+    fn rate_limit_allows_up_to_the_limit_per_window() {
+        let window = Duration::from_secs(10);
+        let start = Instant::now();
+        let mut sent = VecDeque::new();
+        for secs in 0..3 {
+            assert_eq!(check_rate(&mut sent, start + Duration::from_secs(secs), 3, window), Ok(()));
+        }
+        assert_eq!(check_rate(&mut sent, start + Duration::from_secs(4), 3, window), Err(Duration::from_secs(6)));
+        // Refused messages are not recorded
+        assert_eq!(sent.len(), 3);
+    }
+
+    #[test]
+    fn rate_limit_window_slides() {
+        let window = Duration::from_secs(10);
+        let start = Instant::now();
+        let mut sent = VecDeque::new();
+        check_rate(&mut sent, start, 2, window).unwrap();
+        check_rate(&mut sent, start + Duration::from_secs(5), 2, window).unwrap();
+        assert_eq!(check_rate(&mut sent, start + Duration::from_secs(10), 2, window), Ok(()));
+        assert_eq!(check_rate(&mut sent, start + Duration::from_secs(11), 2, window), Err(Duration::from_secs(4)));
+    }
+}
@@ -0,0 +1,72 @@
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+const MAX_PASSWORD_LENGTH: usize = 64;
+
+// A room password, kept only as a salted SHA-256 digest
+pub struct RoomPassword {
+    salt: String,
+    digest: [u8; 32],
+}
+
+impl RoomPassword {
+    // Passwords are 1-64 characters, used exactly as given
+    pub fn new(password: &str) -> Result<Self, String> {
+        if password.is_empty() || password.chars().count() > MAX_PASSWORD_LENGTH {
+            return Err(format!("password must be 1-{} characters", MAX_PASSWORD_LENGTH));
+        }
+        let salt = Uuid::new_v4().simple().to_string();
+        let digest = salted_digest(&salt, password);
+        Ok(RoomPassword { salt, digest })
+    }
+
+    pub fn verify(&self, attempt: &str) -> bool {
+        constant_time_eq(&self.digest, &salted_digest(&self.salt, attempt))
+    }
+}
+
+fn salted_digest(salt: &str, password: &str) -> [u8; 32] {
+    Sha256::digest(format!("{}:{}", salt, password).as_bytes()).into()
+}
+
+// Look at every byte whatever the first difference, so the time taken does not hint at how close a guess was
+fn constant_time_eq(a: &[u8; 32], b: &[u8; 32]) -> bool {
+    a.iter().zip(b.iter()).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verify_accepts_only_the_exact_password() {
+        let password = RoomPassword::new("hunter2").unwrap();
+        assert!(password.verify("hunter2"));
+        assert!(!password.verify("Hunter2"));
+        assert!(!password.verify("hunter2 "));
+        assert!(!password.verify(""));
+    }
+
+    #[test]
+    fn passwords_are_salted() {
+        let (a, b) = (RoomPassword::new("hunter2").unwrap(), RoomPassword::new("hunter2").unwrap());
+        assert_ne!(a.salt, b.salt);
+        assert_ne!(a.digest, b.digest);
+    }
+
+    #[test]
+    fn passwords_must_be_1_to_64_characters() {
+        assert!(RoomPassword::new("").is_err());
+        assert!(RoomPassword::new(&"ä".repeat(MAX_PASSWORD_LENGTH)).is_ok());
+        assert!(RoomPassword::new(&"a".repeat(MAX_PASSWORD_LENGTH + 1)).is_err());
+    }
+
+    #[test]
+    fn constant_time_eq_compares_every_byte() {
+        let digest = salted_digest("salt", "hunter2");
+        assert!(constant_time_eq(&digest, &digest));
+        let mut last = digest;
+        last[31] ^= 1;
+        assert!(!constant_time_eq(&digest, &last));
+    }
+}
@@ -1,4 +1,116 @@
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
 use serde::{Serialize};
+use std::collections::HashMap;
+use crate::engine::game::{Event, PlayerId};
+use crate::engine::phase::RoomPhase;
+use crate::engine::scoring::{MatchFormat, ScoreRow, ScoringMode};
+use crate::engine::settings::{GameSettings, TimeoutPolicy};
+
+// Every message the server emits, tagged by its `event` field
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum ServerEvent {
+    Join(JoinRoomResponse),
+    Leave(JoinRoomResponse),
+    RoomList(RoomListResponse),
+    RoomState(Box<RoomStateResponse>),
+    PhaseChanged(PhaseChangedResponse),
+    SettingsUpdated(SettingsUpdatedResponse),
+    LimitsUpdated(LimitsUpdatedResponse),
+    HostChanged(HostChangedResponse),
+    RoomLocked(RoomLockedResponse),
+    Kicked(KickedResponse),
+    PlayerReady(PlayerReadyResponse),
+    GameStarted(GameStartedResponse),
+    Rematch(RematchResponse),
+    RoundResult(RoundResultResponse),
+    ScoreUpdate(ScoreUpdateResponse),
+    MatchResult(MatchResultResponse),
+    MatchAborted(MatchAbortedResponse),
+    RoundTimer(RoundTimerResponse),
+    CountdownTick(CountdownTickResponse),
+    PlayerForfeited(PlayerForfeitedResponse),
+    MoveAssigned(MoveAssignedResponse),
+    MoveAccepted(MoveAcceptedResponse),
+    RoundProgress(RoundProgressResponse),
+    MoveLocked(MoveLockedResponse),
+    MoveCommitted(MoveCommittedResponse),
+    MoveRevealed(MoveRevealedResponse),
+    PlayerDisconnected(PlayerDisconnectedResponse),
+    PlayerReconnected(PlayerReconnectedResponse),
+    ProfileUpdated(ProfileUpdatedResponse),
+    ChatMessage(ChatMessageResponse),
+    ChatHistory(ChatHistoryResponse),
+    PlayerMuted(PlayerMutedResponse),
+    Error(ErrorResponse),
+}
+
+impl ServerEvent {
+    // Translate an engine event into the message broadcast to the room; `None` for internal events.
+    // `player` looks up the display profile of the players an event lists.
+    pub fn from_game_event(room_id: &str, event: Event, player: &dyn Fn(&PlayerId) -> PlayerRef) -> Option<ServerEvent> {
+        let room_id = room_id.to_string();
+        let refs = |players: &[PlayerId]| players.iter().map(player).collect::<Vec<PlayerRef>>();
+        let event = match event {
+            // Only drive the server's round timers; see `game_loop::publish`
+            Event::CountdownStarted { .. } | Event::RoundOpened { .. } | Event::RevealOpened { .. } => return None,
+            Event::PhaseChanged { from, to } => ServerEvent::PhaseChanged(PhaseChangedResponse { room_id, from, phase: to }),
+            Event::SettingsChanged { settings } => ServerEvent::SettingsUpdated(SettingsUpdatedResponse { room_id, settings }),
+            Event::GameStarted { players, rules, choices, format, mode, game_number } => ServerEvent::GameStarted(GameStartedResponse { room_id, players: refs(&players), rules, choices, format, mode, game_number }),
+            Event::Rematch { next_players, reason, moves } => ServerEvent::Rematch(RematchResponse { room_id, next_players: refs(&next_players), reason: reason.as_str().into(), moves: moves_by_id(moves) }),
+            Event::RoundResult { winner, moves } => ServerEvent::RoundResult(RoundResultResponse { room_id, tie: false, winners: vec![player(&winner)], moves: moves_by_id(moves), round: None, table: None }),
+            Event::RoundScored { round, moves, table } => {
+                // Round winners are the players with the most points this round, unless everyone scored the same
+                let best = table.iter().map(|row| row.points).max().unwrap_or(0);
+                let tie = table.iter().all(|row| row.points == best);
+                let winners = if tie { vec![] } else { table.iter().filter(|row| row.points == best).map(|row| player(&row.player)).collect() };
+                let table = table.into_iter().map(|row| ScoreRowResponse::new(player(&row.player), row)).collect();
+                ServerEvent::RoundResult(RoundResultResponse { room_id, tie, winners, moves: moves_by_id(moves), round: Some(round), table: Some(table) })
+            }
+            Event::ScoreUpdate { scores, games_played } => ServerEvent::ScoreUpdate(ScoreUpdateResponse { room_id, scores: scores_by_id(scores), games_played }),
+            Event::MatchResult { winner, scores } => ServerEvent::MatchResult(MatchResultResponse { room_id, winner: player(&winner), scores: scores_by_id(scores) }),
+            Event::MatchAborted { reason } => ServerEvent::MatchAborted(MatchAbortedResponse { room_id, reason: reason.as_str().into() }),
+            Event::PlayerForfeited { player: id, reason } => ServerEvent::PlayerForfeited(PlayerForfeitedResponse { room_id, player: player(&id), reason: reason.as_str().into() }),
+            Event::MoveAssigned { player: id } => ServerEvent::MoveAssigned(MoveAssignedResponse { room_id, player: player(&id) }),
+            Event::MoveAccepted { player: _, round } => ServerEvent::MoveAccepted(MoveAcceptedResponse { room_id, round }),
+            Event::RoundProgress { round, submitted, waiting } => ServerEvent::RoundProgress(RoundProgressResponse { room_id, round, submitted: refs(&submitted), waiting: refs(&waiting) }),
+            Event::MoveLocked { player: id, locked } => ServerEvent::MoveLocked(MoveLockedResponse { room_id, player: player(&id), locked: refs(&locked) }),
+            Event::MoveCommitted { player: id, hash, committed } => ServerEvent::MoveCommitted(MoveCommittedResponse { room_id, player: player(&id), hash, committed: refs(&committed) }),
+            Event::MoveRevealed { player: id, choice, nonce } => ServerEvent::MoveRevealed(MoveRevealedResponse { room_id, player: player(&id), choice, nonce }),
+        };
+        Some(event)
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_else(|_| "{}".into())
+    }
+}
+
+// A player as listed in events: id plus display profile
+#[derive(Debug, Clone, Serialize)]
+pub struct PlayerRef {
+    pub id: String,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub avatar: Option<String>,
+}
+
+impl PlayerRef {
+    // Name shown for players who never picked one (or are no longer in the room)
+    pub fn unnamed(id: &PlayerId) -> Self {
+        PlayerRef { id: id.to_string(), name: format!("Player {}", &id.simple().to_string()[..6]), avatar: None }
+    }
+}
+
+fn moves_by_id(moves: HashMap<PlayerId, String>) -> HashMap<String, String> {
+    moves.into_iter().map(|(id, choice)| (id.to_string(), choice)).collect()
+}
+
+pub(crate) fn scores_by_id(scores: HashMap<PlayerId, u32>) -> HashMap<String, u32> {
+    scores.into_iter().map(|(id, score)| (id.to_string(), score)).collect()
+}
 
 #[derive(Debug, Serialize)]
 pub struct JoinRoomResponse {
@@ -6,6 +118,12 @@ pub struct JoinRoomResponse {
     pub room_id: Option<String>,
     pub message: Option<String>,
     pub my_id: Option<String>,
+    // The player who joined or left
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub player: Option<PlayerRef>,
+    // Only in the joiner's own successful `join`: reconnect with `?resume_token=` to get this seat back
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resume_token: Option<String>,
 }
 
 #[derive(Debug, Serialize)]
@@ -17,38 +135,296 @@ pub struct RoomListResponse {
 pub struct RoomInfo {
     pub room_id: String,
     pub client_count: usize,
+    pub capacity: usize,
+    pub min_players: usize,
+    pub locked: bool,
+    pub password_protected: bool,
+}
+
+// Everything a client needs to render a room it just joined (or rejoined)
+#[derive(Debug, Serialize)]
+pub struct RoomStateResponse {
+    pub room_id: String,
+    pub my_id: String,
+    pub players: Vec<PlayerStateResponse>,
+    pub host: Option<PlayerRef>,
+    pub locked: bool,
+    pub private: bool,
+    pub password_protected: bool,
+    pub capacity: usize,
+    pub min_players: usize,
+    pub phase: RoomPhase,
+    pub round: u32,
+    pub rules: String,
+    pub choices: Vec<String>,
+    pub settings: GameSettings,
+    // The current (or last) match; empty before the first `start`
+    pub format: MatchFormat,
+    pub mode: ScoringMode,
+    pub match_players: Vec<PlayerRef>,
+    pub active_players: Vec<PlayerRef>,
+    pub submitted: Vec<PlayerRef>, // active players who have moved this round, without their moves
+    // The recipient's own move this round, e.g. after reconnecting
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub my_move: Option<String>,
+    pub scores: HashMap<String, u32>,
+    pub games_played: u32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PlayerStateResponse {
+    #[serde(flatten)]
+    pub player: PlayerRef,
+    pub connected: bool, // false while the seat is held for a dropped connection
+    pub muted: bool,
+    pub ready: bool, // ready-check rooms: ready for the next match
+}
+
+#[derive(Debug, Serialize)]
+pub struct HostChangedResponse {
+    pub room_id: String,
+    pub host: PlayerRef,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RoomLockedResponse {
+    pub room_id: String,
+    pub locked: bool, // locked rooms refuse new players; held seats can still be resumed
+}
+
+#[derive(Debug, Serialize)]
+pub struct PlayerReadyResponse {
+    pub room_id: String,
+    pub player: PlayerRef,
+    pub ready: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct KickedResponse {
+    pub room_id: String,
+    pub player: PlayerRef,
+    // When players from the kicked player's address may join again; absent without a ban
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub banned_until_ms: Option<u64>,
 }
 
 // Game-related responses
+#[derive(Debug, Serialize)]
+pub struct PhaseChangedResponse {
+    pub room_id: String,
+    pub from: RoomPhase,
+    pub phase: RoomPhase,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SettingsUpdatedResponse {
+    pub room_id: String,
+    pub settings: GameSettings,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LimitsUpdatedResponse {
+    pub room_id: String,
+    pub capacity: usize,
+    pub min_players: usize,
+}
+
 #[derive(Debug, Serialize)]
 pub struct GameStartedResponse {
-    pub event: &'static str, // "game_started"
     pub room_id: String,
-    pub players: Vec<String>,
+    pub players: Vec<PlayerRef>,
+    pub rules: String,
+    pub choices: Vec<String>, // moves accepted by the `move` action this game
+    pub format: MatchFormat,
+    pub mode: ScoringMode,
+    pub game_number: u32, // 1-based index of this game (or round-robin round) within the match
 }
 
 #[derive(Debug, Serialize)]
 pub struct RoundResultResponse {
-    pub event: &'static str, // "round_result"
     pub room_id: String,
     pub tie: bool,
-    pub winners: Vec<String>,
-    pub moves: std::collections::HashMap<String, String>,
+    pub winners: Vec<PlayerRef>,
+    pub moves: HashMap<String, String>,
+    // Round-robin mode only: round number and pairwise scoring table
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub round: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub table: Option<Vec<ScoreRowResponse>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ScoreRowResponse {
+    pub player: PlayerRef,
+    pub wins: u32,
+    pub ties: u32,
+    pub losses: u32,
+    pub points: u32,
+    pub total: u32,
+}
+
+impl ScoreRowResponse {
+    fn new(player: PlayerRef, row: ScoreRow) -> Self {
+        ScoreRowResponse { player, wins: row.wins, ties: row.ties, losses: row.losses, points: row.points, total: row.total }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ScoreUpdateResponse {
+    pub room_id: String,
+    pub scores: HashMap<String, u32>, // game wins per player
+    pub games_played: u32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MatchResultResponse {
+    pub room_id: String,
+    pub winner: PlayerRef,
+    pub scores: HashMap<String, u32>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MatchAbortedResponse {
+    pub room_id: String,
+    pub reason: String, // "round_timeout", "no_moves" or "player_left"
+}
+
+#[derive(Debug, Serialize)]
+pub struct RoundTimerResponse {
+    pub room_id: String,
+    pub round: u32,
+    pub deadline_ms: u64, // unix epoch milliseconds, server clock
+    pub timeout_secs: u64,
+    pub policy: TimeoutPolicy,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CountdownTickResponse {
+    pub room_id: String,
+    pub round: u32,
+    pub remaining: u64, // seconds left; 0 means "shoot" and moves are accepted from now on
+    pub server_time_ms: u64,
+    pub shoot_at_ms: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PlayerForfeitedResponse {
+    pub room_id: String,
+    pub player: PlayerRef,
+    pub reason: String, // "timeout", "left", "reveal_mismatch", "invalid_reveal" or "reveal_timeout"
+}
+
+#[derive(Debug, Serialize)]
+pub struct MoveAssignedResponse {
+    pub room_id: String,
+    pub player: PlayerRef, // idle player who was given a random move
+}
+
+#[derive(Debug, Serialize)]
+pub struct MoveAcceptedResponse {
+    pub room_id: String,
+    pub round: u32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RoundProgressResponse {
+    pub room_id: String,
+    pub round: u32,
+    pub submitted: Vec<PlayerRef>, // active players who have moved (or committed), without their moves
+    pub waiting: Vec<PlayerRef>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MoveLockedResponse {
+    pub room_id: String,
+    pub player: PlayerRef,
+    pub locked: Vec<PlayerRef>, // everyone who has locked in this round
+}
+
+#[derive(Debug, Serialize)]
+pub struct MoveCommittedResponse {
+    pub room_id: String,
+    pub player: PlayerRef,
+    pub hash: String,
+    pub committed: Vec<PlayerRef>, // everyone who has committed this round
+}
+
+#[derive(Debug, Serialize)]
+pub struct MoveRevealedResponse {
+    pub room_id: String,
+    pub player: PlayerRef,
+    pub choice: String,
+    pub nonce: String, // lets every client check sha256("{choice}:{nonce}") against the commitment
 }
 
 #[derive(Debug, Serialize)]
 pub struct ErrorResponse {
-    pub event: &'static str, // "error"
     pub room_id: Option<String>,
+    pub code: String, // machine-readable, e.g. "invalid_message" or "game_in_progress"
     pub message: String,
     pub my_id: Option<String>,
 }
 
+impl ErrorResponse {
+    // An error answered over plain HTTP rather than a socket
+    pub fn http(status: StatusCode, room_id: Option<String>, code: &str, message: impl Into<String>) -> Response {
+        let err = ErrorResponse { room_id, code: code.into(), message: message.into(), my_id: None };
+        (status, Json(err)).into_response()
+    }
+}
+
+// Answer to `POST /rooms`
+#[derive(Debug, Serialize)]
+pub struct CreateRoomResponse {
+    pub room_id: String,
+    pub join_url: String,
+}
+
 #[derive(Debug, Serialize)]
 pub struct RematchResponse {
-    pub event: &'static str, // "rematch"
     pub room_id: String,
-    pub next_players: Vec<String>,
+    pub next_players: Vec<PlayerRef>,
     pub reason: String, // e.g., "multiple_winners" or "tie_all"
-    pub moves: std::collections::HashMap<String, String>,
+    pub moves: HashMap<String, String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PlayerDisconnectedResponse {
+    pub room_id: String,
+    pub player: PlayerRef,
+    pub reconnect_deadline_ms: u64, // unix epoch milliseconds; the seat is given up after this
+}
+
+#[derive(Debug, Serialize)]
+pub struct PlayerReconnectedResponse {
+    pub room_id: String,
+    pub player: PlayerRef,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ProfileUpdatedResponse {
+    pub room_id: String,
+    pub player: PlayerRef,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ChatMessageResponse {
+    pub room_id: String,
+    pub id: u64, // increases with every message in the room
+    pub from: PlayerRef,
+    pub message: String,
+    pub sent_at_ms: u64, // unix epoch milliseconds, server clock
+}
+
+#[derive(Debug, Serialize)]
+pub struct PlayerMutedResponse {
+    pub room_id: String,
+    pub player: PlayerRef,
+    pub muted: bool, // false when the player was unmuted
+}
+
+#[derive(Debug, Serialize)]
+pub struct ChatHistoryResponse {
+    pub room_id: String,
+    pub messages: Vec<ChatMessageResponse>, // oldest first
 }
@@ -3,7 +3,6 @@ use axum::extract::{State, WebSocketUpgrade};
 use axum::response::IntoResponse;
 use futures::{SinkExt, StreamExt};
 use uuid::Uuid;
-use crate::server::responses::{RoomInfo, RoomListResponse};
 use crate::server::server::SharedState;
 
 pub async fn rooms_stream(ws: WebSocketUpgrade, State(state): State<SharedState>) -> impl IntoResponse {
@@ -20,12 +19,7 @@ async fn handle_rooms_stream(socket: WebSocket, state: SharedState) {
     // Send initial snapshot
     {
         let app = state.lock().await;
-        let snapshot: Vec<RoomInfo> = app
-            .rooms
-            .iter()
-            .map(|(room_id, room)| RoomInfo { room_id: room_id.clone(), client_count: room.clients.len() })
-            .collect();
-        let init_msg = serde_json::to_string(&RoomListResponse { rooms: snapshot }).unwrap_or_else(|_| "{}".into());
+        let init_msg = app.room_list().to_json();
         if sender.send(Message::Text(init_msg.into())).await.is_err() {
             return;
         }
@@ -1,10 +1,19 @@
+use axum::http::HeaderMap;
 use axum::routing::get;
 use axum::Router;
-use std::collections::{HashMap, HashSet};
-use std::{net::SocketAddr, sync::Arc};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::{Duration, Instant, SystemTime};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
 use tokio::sync::{mpsc, Mutex, Notify};
+use tokio::task::AbortHandle;
 use uuid::Uuid;
-use crate::server::{join_room, rooms_stream};
+use crate::engine::game::{Game, MIN_PLAYERS};
+use crate::engine::phase::RoomPhase;
+use crate::engine::scoring::{MatchFormat, ScoringMode};
+use crate::server::{create_room, game_loop, join_room, rooms_stream};
+use crate::server::password::RoomPassword;
+use crate::server::responses::{scores_by_id, ChatMessageResponse, PlayerRef, PlayerStateResponse, RoomInfo, RoomListResponse, RoomStateResponse, ServerEvent};
 use std::sync::OnceLock;
 
 // Global shutdown notifier for graceful stop from FFI or other callers
@@ -12,31 +21,304 @@ static SHUTDOWN_NOTIFY: OnceLock<Notify> = OnceLock::new();
 
 // Type alias for client sender
 pub(crate) type Tx = mpsc::UnboundedSender<String>;
-pub(crate) type Clients = HashMap<Uuid, Tx>;
+pub(crate) type Clients = HashMap<Uuid, ClientEntry>;
+
+// A player's seat in a room; it outlives a dropped connection for a grace period so the player can resume it
+pub struct ClientEntry {
+    pub tx: Tx,
+    // Secret that lets a new connection take over this seat
+    pub resume_token: String,
+    pub name: Option<String>,
+    pub avatar: Option<String>,
+    // Identifies the connection currently holding the seat, and where it comes from
+    pub connection: Uuid,
+    pub address: IpAddr,
+    // False while the seat is held for a disconnected player
+    pub connected: bool,
+    // Tells the connection to close (after sending what is queued) when another connection takes the seat over or the player is kicked
+    pub close: Arc<Notify>,
+    // When this player's recent chat messages were sent, for the chat rate limit
+    pub chat_sent: VecDeque<Instant>,
+    // When the player first took the seat; the longest-seated player inherits the host role
+    pub joined_at: Instant,
+}
+
+// A kicked player's temporary ban from a room (only with `ServerConfig::ban_addresses`): new players from their address are refused
+pub struct Ban {
+    pub address: IpAddr,
+    pub until: Instant,
+}
 
 // Room state
 pub struct Room {
+    // Tells this room apart from an earlier or later room under the same id
+    pub nonce: Uuid,
     pub clients: Clients,
-    pub game_active: bool,
-    // Stores each player's submitted move for the current round ("rock", "paper", or "scissors")
-    pub moves: HashMap<Uuid, String>,
-    // Current active participants (subset of clients) expected to play this round
-    pub active_players: HashSet<Uuid>,
+    // The client who runs the room (starts matches, changes settings, moderates); the first to join at first
+    pub host: Option<Uuid>,
+    // Locked rooms refuse new players
+    pub locked: bool,
+    // Private rooms are left out of room lists; only players who know the id can join
+    pub private: bool,
+    // New players must give this password to join
+    pub password: Option<RoomPassword>,
+    // Most players the room seats, and fewest a match can start with
+    pub capacity: usize,
+    pub min_players: usize,
+    // Match format and scoring mode for matches started without naming one
+    pub format: MatchFormat,
+    pub mode: ScoringMode,
+    // Players the host has muted; their chat messages are refused
+    pub muted: HashSet<Uuid>,
+    // Ready-check rooms: players ready for the next match
+    pub ready: HashSet<Uuid>,
+    // Players kicked with a ban, by player id
+    pub bans: HashMap<Uuid, Ban>,
+    // Game state machine; its active players are always a subset of `clients`
+    pub game: Game,
+    // Pending deadline of the current round, if the room uses round timeouts
+    pub round_timer: Option<AbortHandle>,
+    // Most recent chat messages, oldest first, replayed to players who join later
+    pub chat_history: VecDeque<ChatMessageResponse>,
+    next_chat_id: u64,
+}
+
+impl Room {
+    pub fn new(game: Game, capacity: usize, min_players: usize) -> Self {
+        Room { nonce: Uuid::new_v4(), clients: HashMap::new(), host: None, locked: false, private: false, password: None, capacity, min_players, format: MatchFormat::default(), mode: ScoringMode::default(), muted: HashSet::new(), ready: HashSet::new(), bans: HashMap::new(), game, round_timer: None, chat_history: VecDeque::new(), next_chat_id: 1 }
+    }
+
+    // The player to hand the host role to: the longest-seated one, preferring players who are online
+    pub fn next_host(&self) -> Option<Uuid> {
+        self.clients.iter().min_by_key(|(_, client)| (!client.connected, client.joined_at)).map(|(id, _)| *id)
+    }
+
+    // Whether a new player giving `attempt` may join; otherwise the error code and message to turn them away with
+    pub fn check_password(&self, attempt: Option<&str>) -> Result<(), (&'static str, &'static str)> {
+        match (&self.password, attempt) {
+            (None, _) => Ok(()),
+            (Some(_), None) => Err(("password_required", "This room needs a password")),
+            (Some(password), Some(attempt)) if password.verify(attempt) => Ok(()),
+            (Some(_), Some(_)) => Err(("wrong_password", "Wrong password")),
+        }
+    }
+
+    // Connected players, in id order
+    pub fn connected_players(&self) -> Vec<Uuid> {
+        let mut players: Vec<Uuid> = self.clients.iter().filter(|(_, client)| client.connected).map(|(id, _)| *id).collect();
+        players.sort();
+        players
+    }
+
+    // Connected players who are ready for the next match, in id order
+    pub fn ready_players(&self) -> Vec<Uuid> {
+        self.connected_players().into_iter().filter(|id| self.ready.contains(id)).collect()
+    }
+
+    // Ready-check rooms: the ready players a match can start with once `quorum` of them (every connected player
+    // when unset), and at least `min_players`, are ready; otherwise how many are ready and how many are needed
+    pub fn ready_to_start(&self, quorum: Option<usize>) -> Result<Vec<Uuid>, (usize, usize)> {
+        let ready = self.ready_players();
+        let needed = quorum.unwrap_or_else(|| self.connected_players().len()).max(self.min_players);
+        if ready.len() < needed {
+            return Err((ready.len(), needed));
+        }
+        Ok(ready)
+    }
+
+    // Time left on the longest ban that `covers` a joining player; expired bans are dropped
+    pub fn ban_remaining(&mut self, now: Instant, covers: impl Fn(&Ban) -> bool) -> Option<Duration> {
+        self.bans.retain(|_, ban| ban.until > now);
+        self.bans.values().filter(|ban| covers(ban)).map(|ban| ban.until - now).max()
+    }
+
+    // Stamp a chat message from `from` and keep it in the room's history (at most `history_len` messages)
+    pub fn post_chat(&mut self, room_id: &str, from: &Uuid, message: String, history_len: usize) -> ChatMessageResponse {
+        let chat = ChatMessageResponse {
+            room_id: room_id.to_string(),
+            id: self.next_chat_id,
+            from: self.player_ref(from),
+            message,
+            sent_at_ms: game_loop::unix_millis(SystemTime::now()),
+        };
+        self.next_chat_id += 1;
+        self.chat_history.push_back(chat.clone());
+        while self.chat_history.len() > history_len {
+            self.chat_history.pop_front();
+        }
+        chat
+    }
+
+    // Send an event to every client in the room
+    pub fn broadcast(&self, event: &ServerEvent) {
+        let json = event.to_json();
+        for client in self.clients.values() {
+            let _ = client.tx.send(json.clone());
+        }
+    }
+
+    // How a player is listed in events; players no longer in the room get a placeholder name
+    pub fn player_ref(&self, id: &Uuid) -> PlayerRef {
+        match self.clients.get(id) {
+            Some(ClientEntry { name: Some(name), avatar, .. }) => PlayerRef { id: id.to_string(), name: name.clone(), avatar: avatar.clone() },
+            Some(client) => PlayerRef { avatar: client.avatar.clone(), ..PlayerRef::unnamed(id) },
+            None => PlayerRef::unnamed(id),
+        }
+    }
+
+    // Snapshot of the room for `client_id`, sent when they join or reconnect
+    pub fn state(&self, room_id: &str, client_id: Uuid) -> ServerEvent {
+        let game = &self.game;
+        let mut players: Vec<PlayerStateResponse> = self.clients.iter().map(|(id, client)| PlayerStateResponse { player: self.player_ref(id), connected: client.connected, muted: self.muted.contains(id), ready: self.ready.contains(id) }).collect();
+        players.sort_by(|a, b| a.player.id.cmp(&b.player.id));
+        let mut active_players: Vec<Uuid> = game.active_players().iter().cloned().collect();
+        active_players.sort();
+        let refs = |players: &[Uuid]| players.iter().map(|id| self.player_ref(id)).collect::<Vec<PlayerRef>>();
+        // Countdown rooms keep who has moved to themselves until the window closes, like `round_progress`
+        let submitted = match game.phase() {
+            RoomPhase::Countdown | RoomPhase::Collecting if game.settings().reveal_at_deadline() => vec![],
+            _ => game.submitted_players(),
+        };
+        ServerEvent::RoomState(Box::new(RoomStateResponse {
+            room_id: room_id.to_string(),
+            my_id: client_id.to_string(),
+            players,
+            host: self.host.map(|id| self.player_ref(&id)),
+            locked: self.locked,
+            private: self.private,
+            password_protected: self.password.is_some(),
+            capacity: self.capacity,
+            min_players: self.min_players,
+            phase: game.phase(),
+            round: game.round(),
+            rules: game.rules().name().to_string(),
+            choices: game.rules().moves().to_vec(),
+            settings: game.settings().clone(),
+            format: game.format(),
+            mode: game.mode(),
+            match_players: refs(game.match_players()),
+            active_players: refs(&active_players),
+            submitted: refs(&submitted),
+            my_move: game.move_of(&client_id).map(str::to_string),
+            scores: scores_by_id(game.scoreboard().scores().clone()),
+            games_played: game.scoreboard().games_played(),
+        }))
+    }
+
+    // Send an event to a single client of the room
+    pub fn send_to(&self, client_id: &Uuid, event: &ServerEvent) {
+        if let Some(client) = self.clients.get(client_id) {
+            let _ = client.tx.send(event.to_json());
+        }
+    }
+}
+
+// Server-wide limits; `start_server` runs with the defaults
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    // Longest chat message accepted, in characters
+    pub max_chat_length: usize,
+    // Chat messages kept per room and replayed to players who join later
+    pub chat_history_len: usize,
+    // Each player may send at most `chat_rate_limit` messages in any `chat_rate_window_secs` seconds
+    pub chat_rate_limit: usize,
+    pub chat_rate_window_secs: u64,
+    // Lowercase words masked out of chat messages
+    pub blocked_words: HashSet<String>,
+    // Upper bound on room capacity; also the capacity of rooms that do not pick one
+    pub max_room_capacity: usize,
+    // Whether joining an unknown room id creates the room; otherwise rooms must be created with `POST /rooms`
+    pub implicit_rooms: bool,
+    // Rooms created with `POST /rooms` are dropped if nobody has joined them within this many seconds
+    pub empty_room_ttl_secs: u64,
+    // Whether `kick` may ban the kicked player's address for a while. Off by default, since players behind one NAT or
+    // reverse proxy share an address; without it `ban_secs` is refused
+    pub ban_addresses: bool,
+    // Header in which a trusted reverse proxy passes on the client's address, e.g. "x-forwarded-for"; its last entry
+    // (the one the proxy added) is used instead of the socket's peer address. Only set it when every connection comes
+    // through the proxy, or clients can pick their own address.
+    pub forwarded_for_header: Option<String>,
+    // Where clients reach the server, e.g. "wss://rps.example.com"; `POST /rooms` answers with a join URL under it,
+    // or with the bare join path when unset
+    pub public_url: Option<String>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig { max_chat_length: 500, chat_history_len: 50, chat_rate_limit: 5, chat_rate_window_secs: 10, blocked_words: HashSet::new(), max_room_capacity: 10, implicit_rooms: true, empty_room_ttl_secs: 300, ban_addresses: false, forwarded_for_header: None, public_url: None }
+    }
+}
+
+impl ServerConfig {
+    // A room's capacity must fit the server's bound, and its minimum must fit the room
+    pub fn check_room_limits(&self, capacity: usize, min_players: usize) -> Result<(), String> {
+        if !(MIN_PLAYERS..=self.max_room_capacity).contains(&capacity) {
+            return Err(format!("capacity must be between {} and {}", MIN_PLAYERS, self.max_room_capacity));
+        }
+        if !(MIN_PLAYERS..=capacity).contains(&min_players) {
+            return Err(format!("min_players must be between {} and the room's capacity ({})", MIN_PLAYERS, capacity));
+        }
+        Ok(())
+    }
+
+    // Where clients join the room `room_id`
+    pub fn join_url(&self, room_id: &str) -> String {
+        let base = self.public_url.as_deref().map(|url| url.trim_end_matches('/')).unwrap_or_default();
+        format!("{}/join/{}", base, room_id)
+    }
+
+    // Where a connection comes from: the address in `forwarded_for_header` if it is set and readable, otherwise `peer`
+    pub fn client_address(&self, headers: &HeaderMap, peer: IpAddr) -> IpAddr {
+        self.forwarded_for_header
+            .as_deref()
+            .and_then(|header| headers.get_all(header).iter().next_back())
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.rsplit(',').next())
+            .and_then(|address| address.trim().parse().ok())
+            .unwrap_or(peer)
+    }
 }
 
 // Composite application state
 pub struct AppState {
+    pub config: ServerConfig,
     pub rooms: HashMap<String, Room>,
     pub room_watchers: HashMap<Uuid, Tx>, // subscribers to room list updates
 }
 
+impl AppState {
+    // Current list of public rooms as sent to `/rooms` watchers
+    pub fn room_list(&self) -> ServerEvent {
+        let rooms: Vec<RoomInfo> = self
+            .rooms
+            .iter()
+            .filter(|(_, room)| !room.private)
+            .map(|(room_id, room)| RoomInfo { room_id: room_id.clone(), client_count: room.clients.len(), capacity: room.capacity, min_players: room.min_players, locked: room.locked, password_protected: room.password.is_some() })
+            .collect();
+        ServerEvent::RoomList(RoomListResponse { rooms })
+    }
+
+    // Notify room watchers about updated rooms list
+    pub fn broadcast_room_list(&self) {
+        let payload = self.room_list().to_json();
+        for watcher_tx in self.room_watchers.values() {
+            let _ = watcher_tx.send(payload.clone());
+        }
+    }
+}
+
 pub type SharedState = Arc<Mutex<AppState>>;
 
 pub async fn start_server() {
+    start_server_with_config(ServerConfig::default()).await;
+}
+
+pub async fn start_server_with_config(config: ServerConfig) {
     // Initialize tracing for logs (ignore error if already set up)
     let _ = tracing_subscriber::fmt::try_init();
 
     let state: SharedState = Arc::new(Mutex::new(AppState {
+        config,
         rooms: HashMap::new(),
         room_watchers: HashMap::new(),
     }));
@@ -44,7 +326,7 @@ pub async fn start_server() {
     // Build our Axum app with the WebSocket route
     let app = Router::new()
         .route("/join/{room_id}", get(join_room::join_room))
-        .route("/rooms", get(rooms_stream::rooms_stream))
+        .route("/rooms", get(rooms_stream::rooms_stream).post(create_room::create_room))
         .with_state(state);
 
     // Run server with graceful shutdown support
@@ -52,8 +334,8 @@ pub async fn start_server() {
     let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
     tracing::info!("listening on {}", addr);
 
-    let notify = SHUTDOWN_NOTIFY.get_or_init(|| Notify::new()).clone();
-    axum::serve(listener, app)
+    let notify = SHUTDOWN_NOTIFY.get_or_init(Notify::new);
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
         .with_graceful_shutdown(async move {
             // Wait until stop_server() is called
             notify.notified().await;
@@ -72,3 +354,167 @@ pub fn stop_server() {
         // Server not started; nothing to stop
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::game::Command;
+    use crate::engine::rules::RuleSet;
+    use crate::engine::settings::GameSettings;
+
+    fn seat(connected: bool, joined_at: Instant) -> ClientEntry {
+        let (tx, _) = mpsc::unbounded_channel();
+        ClientEntry { tx, resume_token: Uuid::new_v4().simple().to_string(), name: None, avatar: None, connection: Uuid::new_v4(), address: "10.0.0.1".parse().unwrap(), connected, close: Arc::new(Notify::new()), chat_sent: VecDeque::new(), joined_at }
+    }
+
+    // A room seating `n` connected players who joined one second apart, in id order
+    fn room_with(settings: GameSettings, n: u128) -> (Room, Vec<Uuid>) {
+        let mut room = Room::new(Game::with_rules(RuleSet::classic(), settings), 10, MIN_PLAYERS);
+        let players: Vec<Uuid> = (1..=n).map(Uuid::from_u128).collect();
+        let start = Instant::now();
+        for (i, id) in players.iter().enumerate() {
+            room.clients.insert(*id, seat(true, start + Duration::from_secs(i as u64)));
+        }
+        (room, players)
+    }
+
+    fn submitted_in_state(room: &Room, client_id: Uuid) -> Vec<String> {
+        let ServerEvent::RoomState(state) = room.state("room", client_id) else { panic!("expected room_state") };
+        state.submitted.iter().map(|player| player.id.clone()).collect()
+    }
+
+    #[test]
+    fn room_state_lists_who_has_moved() {
+        let (mut room, p) = room_with(GameSettings::default(), 2);
+        room.game.apply(Command::Start { players: p.clone(), format: MatchFormat::Single, mode: ScoringMode::Elimination }).unwrap();
+        room.game.apply(Command::Move { player: p[0], choice: "rock".into() }).unwrap();
+        assert_eq!(submitted_in_state(&room, p[1]), vec![p[0].to_string()]);
+    }
+
+    #[test]
+    fn room_state_hides_who_has_moved_until_a_countdown_window_closes() {
+        let settings = GameSettings { countdown_secs: Some(3), ..GameSettings::default() };
+        let (mut room, p) = room_with(settings, 2);
+        room.game.apply(Command::Start { players: p.clone(), format: MatchFormat::Single, mode: ScoringMode::Elimination }).unwrap();
+        assert!(submitted_in_state(&room, p[1]).is_empty());
+        room.game.apply(Command::OpenSubmissions { round: 1 }).unwrap();
+        room.game.apply(Command::Move { player: p[0], choice: "rock".into() }).unwrap();
+        room.game.apply(Command::Move { player: p[1], choice: "paper".into() }).unwrap();
+        assert!(submitted_in_state(&room, p[0]).is_empty());
+        let ServerEvent::RoomState(state) = room.state("room", p[0]) else { panic!("expected room_state") };
+        assert_eq!(state.my_move.as_deref(), Some("rock"));
+    }
+
+    fn headers(values: &[&str]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for value in values {
+            headers.append("x-forwarded-for", value.parse().unwrap());
+        }
+        headers
+    }
+
+    #[test]
+    fn the_longest_seated_connected_player_is_next_host() {
+        let (mut room, p) = room_with(GameSettings::default(), 3);
+        assert_eq!(room.next_host(), Some(p[0]));
+        room.clients.remove(&p[0]);
+        assert_eq!(room.next_host(), Some(p[1]));
+    }
+
+    #[test]
+    fn a_disconnected_host_loses_the_role_to_a_connected_player() {
+        let (mut room, p) = room_with(GameSettings::default(), 3);
+        room.host = Some(p[0]);
+        room.clients.get_mut(&p[0]).unwrap().connected = false;
+        room.clients.get_mut(&p[1]).unwrap().connected = false;
+        assert_eq!(room.next_host(), Some(p[2]));
+    }
+
+    #[test]
+    fn a_room_where_everyone_is_away_keeps_the_longest_seated_player() {
+        let (mut room, p) = room_with(GameSettings::default(), 2);
+        for client in room.clients.values_mut() {
+            client.connected = false;
+        }
+        assert_eq!(room.next_host(), Some(p[0]));
+        room.clients.clear();
+        assert_eq!(room.next_host(), None);
+    }
+
+    #[test]
+    fn a_match_is_ready_once_every_connected_player_is() {
+        let (mut room, p) = room_with(GameSettings::default(), 3);
+        room.ready.extend([p[0], p[1]]);
+        assert_eq!(room.ready_to_start(None), Err((2, 3)));
+        room.ready.insert(p[2]);
+        assert_eq!(room.ready_to_start(None), Ok(p.clone()));
+    }
+
+    #[test]
+    fn a_quorum_of_ready_players_is_enough() {
+        let (mut room, p) = room_with(GameSettings::default(), 4);
+        room.ready.insert(p[0]);
+        assert_eq!(room.ready_to_start(Some(2)), Err((1, 2)));
+        room.ready.insert(p[2]);
+        assert_eq!(room.ready_to_start(Some(2)), Ok(vec![p[0], p[2]]));
+    }
+
+    #[test]
+    fn players_who_disconnect_no_longer_hold_up_the_match() {
+        let (mut room, p) = room_with(GameSettings::default(), 3);
+        room.ready.extend([p[0], p[1]]);
+        room.clients.get_mut(&p[2]).unwrap().connected = false;
+        assert_eq!(room.ready_to_start(None), Ok(vec![p[0], p[1]]));
+        // A ready player who drops sits the match out
+        room.ready.insert(p[2]);
+        assert_eq!(room.ready_to_start(None), Ok(vec![p[0], p[1]]));
+    }
+
+    #[test]
+    fn a_match_needs_the_room_minimum_of_ready_players() {
+        let (mut room, p) = room_with(GameSettings::default(), 3);
+        room.min_players = 3;
+        room.ready.extend([p[0], p[1]]);
+        room.clients.get_mut(&p[2]).unwrap().connected = false;
+        assert_eq!(room.ready_to_start(None), Err((2, 3)));
+        assert_eq!(room.ready_to_start(Some(2)), Err((2, 3)));
+    }
+
+    #[test]
+    fn join_urls_are_paths_unless_a_public_url_is_configured() {
+        assert_eq!(ServerConfig::default().join_url("finals"), "/join/finals");
+        let config = ServerConfig { public_url: Some("wss://rps.example.com/".into()), ..ServerConfig::default() };
+        assert_eq!(config.join_url("finals"), "wss://rps.example.com/join/finals");
+    }
+
+    #[test]
+    fn room_limits_must_fit_the_server_and_the_room() {
+        let config = ServerConfig::default();
+        let max = config.max_room_capacity;
+        assert_eq!(config.check_room_limits(MIN_PLAYERS, MIN_PLAYERS), Ok(()));
+        assert_eq!(config.check_room_limits(max, max), Ok(()));
+        assert!(config.check_room_limits(1, 1).unwrap_err().starts_with("capacity"));
+        assert!(config.check_room_limits(max + 1, MIN_PLAYERS).unwrap_err().starts_with("capacity"));
+        assert!(config.check_room_limits(4, 5).unwrap_err().starts_with("min_players"));
+        assert!(config.check_room_limits(4, 1).unwrap_err().starts_with("min_players"));
+    }
+
+    #[test]
+    fn forwarded_addresses_are_ignored_unless_configured() {
+        let peer: IpAddr = "10.0.0.1".parse().unwrap();
+        assert_eq!(ServerConfig::default().client_address(&headers(&["203.0.113.7"]), peer), peer);
+    }
+
+    #[test]
+    fn the_proxy_added_forwarded_address_is_used() {
+        let config = ServerConfig { forwarded_for_header: Some("x-forwarded-for".into()), ..ServerConfig::default() };
+        let peer: IpAddr = "10.0.0.1".parse().unwrap();
+        let client: IpAddr = "203.0.113.7".parse().unwrap();
+        assert_eq!(config.client_address(&headers(&["203.0.113.7"]), peer), client);
+        // Entries the client sent itself come first
+        assert_eq!(config.client_address(&headers(&["198.51.100.1, 203.0.113.7"]), peer), client);
+        assert_eq!(config.client_address(&headers(&["198.51.100.1", "203.0.113.7"]), peer), client);
+        assert_eq!(config.client_address(&headers(&["not-an-address"]), peer), peer);
+        assert_eq!(config.client_address(&HeaderMap::new(), peer), peer);
+    }
+}